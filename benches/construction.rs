@@ -0,0 +1,156 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crypto::sha2::Sha256;
+use merkle_tree::{Config, Hash, HashScheme, MerkleHasher, MerkleTree};
+
+// To compare with another commit, run
+// `cargo bench --bench construction -- --save-baseline before` on it, then
+// `cargo bench --bench construction -- --baseline before` on this one.
+//
+// Median times from `-- --warm-up-time 1 --measurement-time 5` on a
+// single-core machine with 6 GB of memory. `recursive` is the builder the
+// crate started with, below; `add_leaf` is the fold hasher. The 2^24 row
+// comes from a second run, after the first one ran out of memory there.
+//
+//   leaves  sha256   recursive  fold     recursive  add_leaf
+//   2^10    1.38 ms  0.84 ms    286 us   280 us     2.60 ms
+//   2^12    3.78 ms  3.55 ms    907 us   961 us     14.7 ms
+//   2^14    16.6 ms  22.5 ms    4.68 ms  4.44 ms    78.4 ms
+//   2^16    92.0 ms  99.7 ms    15.3 ms  32.4 ms    359 ms
+//   2^18    366 ms   347 ms     64.1 ms  102 ms     1.70 s
+//   2^20    1.17 s   1.01 s     289 ms   382 ms     5.98 s
+//   2^22    3.67 s   4.13 s     1.10 s   1.62 s     30.4 s
+//   2^24    17.5 s   -          3.47 s   -          179 s
+//
+// With the fold hasher, building 2^16 to 2^22 leaves takes 24-53% less time
+// than the recursive builder. With SHA-256 the hashing dominates, and from
+// 2^16 up the two are within 16% of each other, either way. Below 2^16
+// neither builder is clearly faster: rerunning 2^10-2^14 with
+// `--measurement-time 15` moved the times by up to 30% and reversed the
+// sha256 gap at 2^10 (1.04 ms against 1.32 ms). An earlier comparison with
+// 722559d, which built one `Vec` per level, measured this builder 24%
+// slower for sha256 at 2^10, 57% for sha256 at 2^14 and 22% for fold at
+// 2^14; these runs neither confirm nor explain that, and it is not fixed.
+
+// folds its input into 32 bytes, cheap enough that the benchmarks below
+// measure the tree rather than the hash function
+#[derive(Clone, Default)]
+struct Fold;
+
+impl MerkleHasher for Fold {
+    type Output = [u8; 32];
+
+    fn output_len(&self) -> usize {
+        32
+    }
+
+    fn hash(&mut self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        for (i, byte) in parts.iter().flat_map(|part| part.iter()).enumerate() {
+            out[i % 32] = out[i % 32].rotate_left(3) ^ byte;
+        }
+        out
+    }
+}
+
+fn leaf_hashes(n: usize) -> Vec<Hash> {
+    (0..n as u64).map(|i| Hash::new(&i.to_be_bytes().repeat(4))).collect()
+}
+
+// A node of the builder the crate started with, which kept the value of
+// leaves next to their hash.
+struct RecursiveNode {
+    #[allow(dead_code)]
+    value: Option<Vec<u8>>,
+    hash: Hash,
+}
+
+// The original `build_parent_nodes`, ported from hex strings and
+// `crypto::digest::Digest` to `Hash` and `MerkleHasher` but otherwise as it
+// was: it recurses once per level, collecting each level into a vector of
+// references to pair it up, and takes a power of two children.
+fn recursive_parent_nodes<H>(children: &[RecursiveNode], hasher: &mut H) -> Vec<RecursiveNode>
+    where H: MerkleHasher,
+{
+    let mut parent_nodes = vec![];
+
+    for pairs in children.iter().collect::<Vec<_>>().chunks(2) {
+        let hash = HashScheme::Unprefixed.hash_internal(&pairs[0].hash, &pairs[1].hash, hasher);
+        parent_nodes.push(RecursiveNode { value: None, hash });
+    }
+
+    if parent_nodes.len() > 1 {
+        let new_parents = recursive_parent_nodes(&parent_nodes, hasher);
+        parent_nodes.extend(new_parents);
+    }
+    parent_nodes
+}
+
+fn recursive_tree<H>(hashes: &[Hash], mut hasher: H) -> Vec<RecursiveNode>
+    where H: MerkleHasher,
+{
+    let mut nodes: Vec<RecursiveNode> = hashes.iter().map(|hash| RecursiveNode { value: None, hash: hash.clone() }).collect();
+    let parent_nodes = recursive_parent_nodes(&nodes, &mut hasher);
+    nodes.extend(parent_nodes);
+    nodes
+}
+
+// Largest tree the original builder is run on: at 2^24 leaves it needs
+// more than the 6 GB of the machine the results above were measured on.
+const RECURSIVE_MAX_LOG: usize = 22;
+
+// builds the levels above leaves hashed beforehand, so that only the
+// construction of the levels is measured; the `recursive` inputs run the
+// original builder over the same leaves
+fn from_hashes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("from_hashes");
+    group.sample_size(10);
+    for log in (10..=24).step_by(2) {
+        let hashes = leaf_hashes(1 << log);
+        group.throughput(Throughput::Elements(hashes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("sha256", log), &hashes, |b, hashes| {
+            b.iter(|| MerkleTree::<Sha256, Vec<u8>>::from_hashes(hashes, Sha256::new(), Config::default()).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("fold", log), &hashes, |b, hashes| {
+            b.iter(|| MerkleTree::<Fold, Vec<u8>>::from_hashes(hashes, Fold, Config::default()).unwrap())
+        });
+
+        if log > RECURSIVE_MAX_LOG {
+            continue;
+        }
+        let root = recursive_tree(&hashes, Fold).pop().unwrap().hash;
+        let mt = MerkleTree::<Fold, Vec<u8>>::from_hashes(&hashes, Fold, Config::default()).unwrap();
+        assert_eq!(mt.root_hash(), Ok(&root));
+        drop(mt);
+
+        group.bench_with_input(BenchmarkId::new("recursive_sha256", log), &hashes, |b, hashes| {
+            b.iter(|| recursive_tree(hashes, Sha256::new()))
+        });
+        group.bench_with_input(BenchmarkId::new("recursive_fold", log), &hashes, |b, hashes| {
+            b.iter(|| recursive_tree(hashes, Fold))
+        });
+    }
+    group.finish();
+}
+
+// appends one leaf at a time, which regrows the buffer as the tree doubles
+fn add_leaf(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add_leaf");
+    group.sample_size(10);
+    for log in (10..=24).step_by(2) {
+        let hashes = leaf_hashes(1 << log);
+        group.throughput(Throughput::Elements(hashes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("fold", log), &hashes, |b, hashes| {
+            b.iter(|| {
+                let mut mt = MerkleTree::<Fold, Vec<u8>>::from_hashes(&hashes[..1], Fold, Config::default()).unwrap();
+                for hash in &hashes[1..] {
+                    mt.add_leaf_hash(hash.clone()).unwrap();
+                }
+                mt
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, from_hashes, add_leaf);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crypto::sha2::Sha256;
+use merkle_tree::MerkleTree;
+
+fn leaves(n: usize) -> Vec<Vec<u8>> {
+    (0..n as u64).map(|i| i.to_be_bytes().repeat(8)).collect()
+}
+
+fn construction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("construction");
+    group.sample_size(10);
+    for &log in [10, 14, 18].iter() {
+        let values = leaves(1 << log);
+        group.throughput(Throughput::Elements(values.len() as u64));
+        group.bench_with_input(BenchmarkId::new("sequential", log), &values, |b, values| {
+            b.iter(|| MerkleTree::from_leaves(values, Sha256::new()).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("parallel", log), &values, |b, values| {
+            b.iter(|| MerkleTree::par_from_leaves(values, Sha256::new()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, construction);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Index};
+
+/// Derives `merkle_tree::Leaf` for a struct: its leaf bytes are its fields,
+/// in declaration order, each written with `Leaf::write_field`.
+#[proc_macro_derive(Leaf)]
+pub fn derive_leaf(input: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(input as DeriveInput);
+
+    let fields = match &input.data {
+        Data::Struct(data) => write_fields(&data.fields),
+        Data::Enum(_) | Data::Union(_) => {
+            return syn::Error::new_spanned(&input.ident, "Leaf can only be derived for structs")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::merkle_tree::Leaf));
+    }
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let expanded = quote! {
+        impl #impl_generics ::merkle_tree::Leaf for #name #ty_generics #where_clause {
+            fn leaf_bytes(&self) -> ::std::borrow::Cow<'_, [u8]> {
+                let mut out = ::std::vec::Vec::new();
+                #fields
+                ::std::borrow::Cow::Owned(out)
+            }
+        }
+    };
+    expanded.into()
+}
+
+fn write_fields(fields: &Fields) -> TokenStream2 {
+    let writes = fields.iter().enumerate().map(|(i, field)| {
+        let member = match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = Index::from(i);
+                quote!(#index)
+            }
+        };
+        quote! {
+            ::merkle_tree::Leaf::write_field(&self.#member, &mut out);
+        }
+    });
+    quote!(#(#writes)*)
+}
@@ -0,0 +1,433 @@
+use std::convert::TryFrom;
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+
+use crate::hash::{Hash, HashScheme};
+use crate::merkle_tree::{Config, MerkleTree};
+
+/// SHA-256 applied twice, as Bitcoin hashes transactions, blocks and
+/// merkle nodes.
+#[derive(Clone, Copy)]
+pub struct DoubleSha256(Sha256);
+
+impl DoubleSha256 {
+    pub fn new() -> Self {
+        DoubleSha256(Sha256::new())
+    }
+}
+
+impl Default for DoubleSha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Digest for DoubleSha256 {
+    fn input(&mut self, input: &[u8]) {
+        self.0.input(input);
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        let mut first = [0u8; 32];
+        self.0.result(&mut first);
+        let mut second = Sha256::new();
+        second.input(&first);
+        second.result(out);
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+    }
+
+    fn output_bits(&self) -> usize {
+        256
+    }
+
+    fn block_size(&self) -> usize {
+        64
+    }
+}
+
+/// The merkle root of a block over its `txids`, in internal byte order:
+/// the reverse of how block explorers display them. This is the tree of
+/// `Config::bitcoin()` with the txids standing in for the leaf hashes.
+pub fn bitcoin_merkle_root(txids: &[Hash]) -> Result<Hash, &'static str> {
+    let mt: MerkleTree<DoubleSha256, Vec<u8>> = MerkleTree::from_hashes(txids, DoubleSha256::new(), Config::bitcoin())?;
+    mt.root_hash().cloned()
+}
+
+/// The partial merkle tree of BIP 37: the txids of some of a block's
+/// transactions, with just enough of the tree to link them to its merkle
+/// root. `flags` tells, depth first, which nodes are expanded and which
+/// are given by hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartialMerkleTree {
+    transactions: u32,
+    hashes: Vec<Hash>,
+    flags: Vec<bool>,
+}
+
+impl PartialMerkleTree {
+    pub fn new(transactions: u32, hashes: Vec<Hash>, flags: Vec<bool>) -> Self {
+        PartialMerkleTree {
+            transactions,
+            hashes,
+            flags,
+        }
+    }
+
+    /// Builds the partial tree proving the txids whose `matches` entry is
+    /// set. Fails on more txids than the 32-bit transaction count holds.
+    pub fn from_txids(txids: &[Hash], matches: &[bool]) -> Result<Self, &'static str> {
+        if txids.is_empty() || txids.len() != matches.len() {
+            return Err("Matches must cover every transaction");
+        }
+
+        let transactions = u32::try_from(txids.len()).map_err(|_| "Too many transactions for a block")?;
+        let mut tree = PartialMerkleTree::new(transactions, vec![], vec![]);
+        let mut hasher = DoubleSha256::new();
+        tree.build(tree.height(), 0, txids, matches, &mut hasher);
+        Ok(tree)
+    }
+
+    pub fn transactions(&self) -> u32 {
+        self.transactions
+    }
+
+    pub fn hashes(&self) -> &[Hash] {
+        &self.hashes
+    }
+
+    pub fn flags(&self) -> &[bool] {
+        &self.flags
+    }
+
+    /// Walks the tree, returning the merkle root it implies and appending
+    /// the matched txids, with their positions in the block, to `matches`.
+    pub fn extract_matches(&self, matches: &mut Vec<(usize, Hash)>) -> Result<Hash, &'static str> {
+        if self.transactions == 0 {
+            return Err("Partial merkle tree has no transactions");
+        }
+        if self.hashes.len() > self.transactions as usize || self.flags.len() < self.hashes.len() {
+            return Err("Partial merkle tree is malformed");
+        }
+
+        let mut hasher = DoubleSha256::new();
+        let mut walk = Walk { bits: 0, hashes: 0, matches };
+        let root = self.extract(self.height(), 0, &mut walk, &mut hasher)?;
+
+        // every hash must be used, and flags only padded to a whole byte
+        if walk.hashes != self.hashes.len() || walk.bits.div_ceil(8) != self.flags.len().div_ceil(8) {
+            return Err("Partial merkle tree has unused data");
+        }
+        Ok(root)
+    }
+
+    /// BIP 37 encoding: the transaction count, the hashes and the flag
+    /// bits packed into bytes, least significant bit first.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = self.transactions.to_le_bytes().to_vec();
+        write_compact_size(self.hashes.len(), &mut out);
+        for hash in &self.hashes {
+            out.extend_from_slice(hash.as_bytes());
+        }
+
+        let mut flag_bytes = vec![0u8; self.flags.len().div_ceil(8)];
+        for (i, _) in self.flags.iter().enumerate().filter(|(_, &flag)| flag) {
+            flag_bytes[i / 8] |= 1 << (i % 8);
+        }
+        write_compact_size(flag_bytes.len(), &mut out);
+        out.extend_from_slice(&flag_bytes);
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, &'static str> {
+        let mut reader = Reader(data);
+        let tree = Self::read(&mut reader)?;
+        reader.finish()?;
+        Ok(tree)
+    }
+
+    fn read(reader: &mut Reader<'_>) -> Result<Self, &'static str> {
+        let mut transactions = [0u8; 4];
+        transactions.copy_from_slice(reader.take(4)?);
+
+        let count = reader.compact_size()?;
+        let mut hashes = Vec::with_capacity(count.min(reader.0.len() / 32));
+        for _ in 0..count {
+            hashes.push(Hash::new(reader.take(32)?));
+        }
+
+        let count = reader.compact_size()?;
+        let flags = reader
+            .take(count)?
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| byte >> i & 1 == 1))
+            .collect();
+
+        Ok(PartialMerkleTree::new(u32::from_le_bytes(transactions), hashes, flags))
+    }
+
+    fn height(&self) -> usize {
+        (self.transactions as usize).next_power_of_two().trailing_zeros() as usize
+    }
+
+    // number of nodes at `height`, the leaves being at height 0
+    fn width(&self, height: usize) -> usize {
+        (self.transactions as usize + (1 << height) - 1) >> height
+    }
+
+    fn build(&mut self, height: usize, pos: usize, txids: &[Hash], matches: &[bool], hasher: &mut DoubleSha256) {
+        let start = pos << height;
+        let end = ((pos + 1) << height).min(txids.len());
+        let parent_of_match = matches[start..end].iter().any(|&m| m);
+        self.flags.push(parent_of_match);
+
+        if height == 0 || !parent_of_match {
+            let hash = self.subtree_hash(height, pos, txids, hasher);
+            self.hashes.push(hash);
+        } else {
+            self.build(height - 1, pos * 2, txids, matches, hasher);
+            if pos * 2 + 1 < self.width(height - 1) {
+                self.build(height - 1, pos * 2 + 1, txids, matches, hasher);
+            }
+        }
+    }
+
+    fn subtree_hash(&self, height: usize, pos: usize, txids: &[Hash], hasher: &mut DoubleSha256) -> Hash {
+        if height == 0 {
+            return txids[pos].clone();
+        }
+        let left = self.subtree_hash(height - 1, pos * 2, txids, hasher);
+        let right = if pos * 2 + 1 < self.width(height - 1) {
+            self.subtree_hash(height - 1, pos * 2 + 1, txids, hasher)
+        } else {
+            left.clone()
+        };
+        HashScheme::Unprefixed.hash_internal(&left, &right, hasher)
+    }
+
+    fn extract(&self, height: usize, pos: usize, walk: &mut Walk<'_>, hasher: &mut DoubleSha256) -> Result<Hash, &'static str> {
+        let flag = *self.flags.get(walk.bits).ok_or("Partial merkle tree is missing flags")?;
+        walk.bits += 1;
+
+        if height == 0 || !flag {
+            let hash = self.hashes.get(walk.hashes).ok_or("Partial merkle tree is missing hashes")?.clone();
+            walk.hashes += 1;
+            if height == 0 && flag {
+                walk.matches.push((pos, hash.clone()));
+            }
+            return Ok(hash);
+        }
+
+        let left = self.extract(height - 1, pos * 2, walk, hasher)?;
+        let right = if pos * 2 + 1 < self.width(height - 1) {
+            let right = self.extract(height - 1, pos * 2 + 1, walk, hasher)?;
+            // a right node equal to its left sibling would let a tree with
+            // a duplicated last transaction pass for another (CVE-2012-2459)
+            if right == left {
+                return Err("Partial merkle tree has a duplicated node");
+            }
+            right
+        } else {
+            left.clone()
+        };
+        Ok(HashScheme::Unprefixed.hash_internal(&left, &right, hasher))
+    }
+}
+
+// progress through a partial merkle tree's flags and hashes
+struct Walk<'a> {
+    bits: usize,
+    hashes: usize,
+    matches: &'a mut Vec<(usize, Hash)>,
+}
+
+/// A `merkleblock` message: a block header and the partial merkle tree of
+/// the transactions matching a filter, for SPV clients.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleBlock {
+    header: [u8; 80],
+    tree: PartialMerkleTree,
+}
+
+impl MerkleBlock {
+    pub fn new(header: [u8; 80], tree: PartialMerkleTree) -> Self {
+        MerkleBlock {
+            header,
+            tree,
+        }
+    }
+
+    pub fn header(&self) -> &[u8; 80] {
+        &self.header
+    }
+
+    pub fn tree(&self) -> &PartialMerkleTree {
+        &self.tree
+    }
+
+    /// The block hash, in internal byte order.
+    pub fn block_hash(&self) -> Hash {
+        let mut hasher = DoubleSha256::new();
+        hasher.input(&self.header);
+        Hash::from_digest(&mut hasher)
+    }
+
+    /// The merkle root committed to by the header, in internal byte order.
+    pub fn merkle_root(&self) -> Hash {
+        Hash::new(&self.header[36..68])
+    }
+
+    /// The matched txids and their positions in the block, once the
+    /// partial tree is checked against the header's merkle root.
+    pub fn matches(&self) -> Result<Vec<(usize, Hash)>, &'static str> {
+        let mut matches = vec![];
+        let root = self.tree.extract_matches(&mut matches)?;
+        if root != self.merkle_root() {
+            return Err("Partial merkle tree does not match the header");
+        }
+        Ok(matches)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = self.header.to_vec();
+        out.extend(self.tree.encode());
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, &'static str> {
+        let mut reader = Reader(data);
+        let mut header = [0u8; 80];
+        header.copy_from_slice(reader.take(80)?);
+        let tree = PartialMerkleTree::read(&mut reader)?;
+        reader.finish()?;
+        Ok(MerkleBlock::new(header, tree))
+    }
+}
+
+fn write_compact_size(n: usize, out: &mut Vec<u8>) {
+    match n {
+        0..=0xfc => out.push(n as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&(n as u64).to_le_bytes());
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], &'static str> {
+        if self.0.len() < n {
+            return Err("Unexpected end of data");
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn compact_size(&mut self) -> Result<usize, &'static str> {
+        let first = self.take(1)?[0];
+        let len = match first {
+            0xfd => 2,
+            0xfe => 4,
+            0xff => 8,
+            _ => return Ok(first as usize),
+        };
+        let n = self.take(len)?.iter().rev().fold(0u64, |n, &b| n << 8 | b as u64);
+        let min = match len {
+            2 => 0xfd,
+            4 => 0x10000,
+            _ => 0x1_0000_0000,
+        };
+        if n < min {
+            return Err("Non-canonical compact size");
+        }
+        Ok(n as usize)
+    }
+
+    fn finish(self) -> Result<(), &'static str> {
+        if !self.0.is_empty() {
+            return Err("Trailing bytes after message");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txid(hex: &str) -> Hash {
+        Hash::from_hex(hex).unwrap().reversed()
+    }
+
+    #[test]
+    fn test_double_sha256() {
+        let mut hasher = DoubleSha256::new();
+        hasher.input(b"hello");
+        let hash = Hash::from_digest(&mut hasher);
+        assert_eq!(hash.to_hex(), "9595c9df90075148eb06860365df33584b75bff782a510c6cd4883a419833d50");
+    }
+
+    #[test]
+    fn test_merkle_root_single() {
+        let genesis = txid("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b");
+        let txids = vec![genesis.clone()];
+        assert_eq!(bitcoin_merkle_root(&txids), Ok(genesis));
+        assert!(bitcoin_merkle_root(&[]).is_err());
+    }
+
+    #[test]
+    fn test_partial_merkle_tree() {
+        let txids: Vec<Hash> = (0..7u8).map(|i| Hash::new(&[i; 32])).collect();
+        let root = bitcoin_merkle_root(&txids).unwrap();
+
+        for mask in 0..(1 << 7) {
+            let matches: Vec<bool> = (0..7).map(|i| mask >> i & 1 == 1).collect();
+            let tree = PartialMerkleTree::from_txids(&txids, &matches).unwrap();
+            let decoded = PartialMerkleTree::decode(&tree.encode()).unwrap();
+
+            let mut matched = vec![];
+            assert_eq!(decoded.extract_matches(&mut matched), Ok(root.clone()));
+            let expected: Vec<(usize, Hash)> = (0..7).filter(|&i| matches[i]).map(|i| (i, txids[i].clone())).collect();
+            assert_eq!(matched, expected);
+        }
+    }
+
+    #[test]
+    fn test_partial_merkle_tree_malformed() {
+        let txids: Vec<Hash> = (0..3u8).map(|i| Hash::new(&[i; 32])).collect();
+        let tree = PartialMerkleTree::from_txids(&txids, &[false, false, true]).unwrap();
+
+        let mut hashes = tree.hashes().to_vec();
+        hashes.push(hashes[0].clone());
+        let extra_hash = PartialMerkleTree::new(3, hashes, tree.flags().to_vec());
+        assert!(extra_hash.extract_matches(&mut vec![]).is_err());
+
+        let mut flags = tree.flags().to_vec();
+        flags.extend(vec![false; 8]);
+        let extra_byte = PartialMerkleTree::new(3, tree.hashes().to_vec(), flags);
+        assert!(extra_byte.extract_matches(&mut vec![]).is_err());
+
+        let short = PartialMerkleTree::new(3, tree.hashes()[1..].to_vec(), tree.flags().to_vec());
+        assert!(short.extract_matches(&mut vec![]).is_err());
+
+        // [a, b, c] and [a, b, c, c] share a root; the latter is refused
+        let mut duplicated = txids.clone();
+        duplicated.push(txids[2].clone());
+        let tree = PartialMerkleTree::from_txids(&duplicated, &[false, false, true, true]).unwrap();
+        assert!(tree.extract_matches(&mut vec![]).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt;
+
+use crate::hasher::MerkleHasher;
+use crate::leaf::Leaf;
+
+/// A binary digest, as produced by the tree's hasher. Its length is the
+/// hasher's `output_len()`; hex is only used to display or parse it.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Hash(Box<[u8]>);
+
+impl Hash {
+    pub fn new(bytes: &[u8]) -> Self {
+        Hash(bytes.into())
+    }
+
+    pub fn zero(len: usize) -> Self {
+        Hash(vec![0u8; len].into_boxed_slice())
+    }
+
+    /// Finalizes a `rust-crypto` hasher into a `Hash`. The hasher is not
+    /// reset.
+    #[cfg(feature = "rust-crypto")]
+    pub fn from_digest<H>(hasher: &mut H) -> Self
+        where H: crypto::digest::Digest,
+    {
+        let mut bytes = vec![0u8; hasher.output_bytes()];
+        hasher.result(&mut bytes);
+        Hash(bytes.into_boxed_slice())
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Self, &'static str> {
+        if hex.len() & 1 == 1 {
+            return Err("Hex string must have an even length");
+        }
+
+        hex.as_bytes()
+            .chunks(2)
+            .map(|pair| Ok(hex_value(pair[0])? << 4 | hex_value(pair[1])?))
+            .collect::<Result<Vec<u8>, &'static str>>()
+            .map(|bytes| Hash(bytes.into_boxed_slice()))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.to_string()
+    }
+
+    /// The same bytes in reverse order, as Bitcoin displays txids and
+    /// block hashes.
+    pub fn reversed(&self) -> Self {
+        Hash(self.0.iter().rev().cloned().collect())
+    }
+}
+
+fn hex_value(c: u8) -> Result<u8, &'static str> {
+    match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err("Invalid hex character"),
+    }
+}
+
+/// How leaves and internal nodes are fed to the hasher.
+///
+/// `Unprefixed` hashes leaf bytes and child hashes as they are, which lets
+/// an internal node's preimage pass for a leaf (a second-preimage attack).
+/// `DomainSeparated` follows RFC 6962 and prefixes leaves with `0x00` and
+/// internal nodes with `0x01`. `Unprefixed` is kept as the default so
+/// existing roots stay the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HashScheme {
+    #[default]
+    Unprefixed,
+    DomainSeparated,
+}
+
+const LEAF_PREFIX: u8 = 0x00;
+const INTERNAL_PREFIX: u8 = 0x01;
+
+impl HashScheme {
+    pub fn hash_leaf<H, T>(self, v: &T, hasher: &mut H) -> Hash
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        self.hash_leaf_bytes(&v.leaf_bytes(), hasher)
+    }
+
+    /// `hash_leaf` for a leaf the hasher may reject, such as bytes that
+    /// are not a field element for `Poseidon`, see
+    /// `MerkleHasher::check_input`.
+    pub fn try_hash_leaf<H, T>(self, v: &T, hasher: &mut H) -> Result<Hash, &'static str>
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        let bytes = v.leaf_bytes();
+        hasher.check_input(&bytes)?;
+        Ok(self.hash_leaf_bytes(&bytes, hasher))
+    }
+
+    fn hash_leaf_bytes<H>(self, bytes: &[u8], hasher: &mut H) -> Hash
+        where H: MerkleHasher,
+    {
+        let hash = match self {
+            HashScheme::Unprefixed => hasher.hash_leaf(bytes),
+            HashScheme::DomainSeparated => hasher.hash(&[&[LEAF_PREFIX], bytes]),
+        };
+        H::into_hash(hash)
+    }
+
+    pub fn hash_internal<H>(self, left: &Hash, right: &Hash, hasher: &mut H) -> Hash
+        where H: MerkleHasher,
+    {
+        let hash = match self {
+            HashScheme::Unprefixed => hasher.hash_pair(left.as_bytes(), right.as_bytes()),
+            HashScheme::DomainSeparated => hasher.hash(&[&[INTERNAL_PREFIX], left.as_bytes(), right.as_bytes()]),
+        };
+        H::into_hash(hash)
+    }
+
+    /// Hashes the children of a node of a k-ary tree, left to right. Two
+    /// children hash as `hash_internal` does.
+    pub fn hash_children<H>(self, children: &[&Hash], hasher: &mut H) -> Hash
+        where H: MerkleHasher,
+    {
+        if let [left, right] = children {
+            return self.hash_internal(left, right, hasher);
+        }
+
+        let mut parts: Vec<&[u8]> = Vec::with_capacity(children.len() + 1);
+        if self == HashScheme::DomainSeparated {
+            parts.push(&[INTERNAL_PREFIX]);
+        }
+        parts.extend(children.iter().map(|child| child.as_bytes()));
+        H::into_hash(hasher.hash(&parts))
+    }
+}
+
+impl AsRef<[u8]> for Hash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Hash {
+    fn from(bytes: Vec<u8>) -> Self {
+        Hash(bytes.into_boxed_slice())
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(self, f)
+    }
+}
+
+impl fmt::LowerHex for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hash({})", self)
+    }
+}
+
+#[cfg(all(test, feature = "rust-crypto"))]
+mod tests {
+    use crypto::digest::Digest;
+    use crypto::sha2::Sha256;
+    use super::*;
+
+    #[test]
+    fn test_from_digest() {
+        let mut hasher = Sha256::new();
+        hasher.input(b"tea");
+        let hash = Hash::from_digest(&mut hasher);
+
+        assert_eq!(hash.len(), 32);
+        assert_eq!(hash.to_hex(), "a9f74d1ec36ebdeb2da3f6e5868090cd2a2d20b3dcca7b62f60304b1d3d9ef42");
+        assert_eq!(format!("{:?}", hash), "Hash(a9f74d1ec36ebdeb2da3f6e5868090cd2a2d20b3dcca7b62f60304b1d3d9ef42)");
+    }
+
+    #[test]
+    fn test_domain_separation() {
+        let mut hasher = Sha256::new();
+        let leaf = HashScheme::DomainSeparated.hash_leaf("tea", &mut hasher);
+        assert_eq!(leaf.to_hex(), "fbae8de98ede70870b432b82f238a72ca1d1b008cd0694f89f59ce4fc2897fd2");
+        assert_ne!(leaf, HashScheme::Unprefixed.hash_leaf("tea", &mut hasher));
+
+        let right = HashScheme::DomainSeparated.hash_leaf("coffee", &mut hasher);
+        let parent = HashScheme::DomainSeparated.hash_internal(&leaf, &right, &mut hasher);
+        assert_eq!(parent.to_hex(), "48cde61125df0fd8ff8606191ddef660e51a36a451c3dfd78e6a5c23c1fe33ad");
+        assert_ne!(parent, HashScheme::Unprefixed.hash_internal(&leaf, &right, &mut hasher));
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let hash = Hash::from_hex("00ff10AB").unwrap();
+        assert_eq!(hash.as_bytes(), &[0x00, 0xff, 0x10, 0xab]);
+        assert_eq!(hash.to_hex(), "00ff10ab");
+
+        assert!(Hash::from_hex("abc").is_err());
+        assert!(Hash::from_hex("zz").is_err());
+        assert!(Hash::from_hex("").unwrap().is_empty());
+    }
+}
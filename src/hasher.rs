@@ -0,0 +1,245 @@
+use crate::hash::Hash;
+
+/// The hash function trees and proofs are built with.
+///
+/// Byte-oriented hashers only need `hash`; the leaf and pair hashes default
+/// to hashing their input as it is. Hashers over other domains, such as
+/// field elements, override `hash_leaf` and `hash_pair`. Domain separation
+/// (`HashScheme::DomainSeparated`) goes through `hash`, with the prefix
+/// byte as the first part.
+pub trait MerkleHasher {
+    /// The digest this hasher produces. Trees store it as a `Hash`.
+    type Output: AsRef<[u8]>;
+
+    /// Length in bytes of `Output`.
+    fn output_len(&self) -> usize;
+
+    /// Hashes the concatenation of `parts`.
+    fn hash(&mut self, parts: &[&[u8]]) -> Self::Output;
+
+    /// Hashes a leaf's bytes.
+    fn hash_leaf(&mut self, data: &[u8]) -> Self::Output {
+        self.hash(&[data])
+    }
+
+    /// Hashes two sibling hashes into their parent.
+    fn hash_pair(&mut self, left: &[u8], right: &[u8]) -> Self::Output {
+        self.hash(&[left, right])
+    }
+
+    /// Checks that `data` can be hashed, as a leaf's bytes or a node's
+    /// hash. Hashers over a restricted domain, such as `Poseidon` over
+    /// field elements, reject the rest here, so that trees return an error
+    /// where `hash` would panic. Any input is accepted by default.
+    fn check_input(&self, _data: &[u8]) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    /// Converts a digest into the `Hash` a tree stores, by copying it
+    /// unless it already is one.
+    fn into_hash(output: Self::Output) -> Hash {
+        Hash::new(output.as_ref())
+    }
+}
+
+/// Any hasher of the `rust-crypto` crate, such as `crypto::sha2::Sha256`.
+#[cfg(feature = "rust-crypto")]
+impl<D> MerkleHasher for D
+    where D: crypto::digest::Digest,
+{
+    type Output = Hash;
+
+    fn output_len(&self) -> usize {
+        self.output_bytes()
+    }
+
+    fn hash(&mut self, parts: &[&[u8]]) -> Hash {
+        self.reset();
+        for part in parts {
+            self.input(part);
+        }
+        Hash::from_digest(self)
+    }
+
+    fn into_hash(output: Hash) -> Hash {
+        output
+    }
+}
+
+/// Adapts a RustCrypto `digest::Digest`, such as `sha2::Sha256`, to a
+/// `MerkleHasher`.
+#[cfg(feature = "digest")]
+#[derive(Clone, Debug, Default)]
+pub struct DigestHasher<D>(D);
+
+#[cfg(feature = "digest")]
+impl<D> DigestHasher<D>
+    where D: digest::Digest,
+{
+    pub fn new() -> Self {
+        DigestHasher(D::new())
+    }
+}
+
+#[cfg(feature = "digest")]
+impl<D> MerkleHasher for DigestHasher<D>
+    where D: digest::Digest,
+{
+    type Output = digest::Output<D>;
+
+    fn output_len(&self) -> usize {
+        <D as digest::Digest>::output_size()
+    }
+
+    fn hash(&mut self, parts: &[&[u8]]) -> Self::Output {
+        for part in parts {
+            self.0.update(part);
+        }
+        std::mem::replace(&mut self.0, D::new()).finalize()
+    }
+}
+
+// a backend over a RustCrypto hasher with 32-byte output
+macro_rules! digest_backend {
+    ($(#[$attr:meta])* $name:ident, $inner:path, $feature:literal) => {
+        $(#[$attr])*
+        #[cfg(feature = $feature)]
+        #[derive(Clone, Debug, Default)]
+        pub struct $name($inner);
+
+        #[cfg(feature = $feature)]
+        impl $name {
+            pub fn new() -> Self {
+                Self::default()
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl MerkleHasher for $name {
+            type Output = [u8; 32];
+
+            fn output_len(&self) -> usize {
+                32
+            }
+
+            fn hash(&mut self, parts: &[&[u8]]) -> [u8; 32] {
+                use digest::Digest;
+
+                for part in parts {
+                    self.0.update(part);
+                }
+                self.0.finalize_reset().into()
+            }
+        }
+    };
+}
+
+digest_backend!(
+    /// SHA-256, as used by Certificate Transparency and Bitcoin.
+    Sha256, sha2::Sha256, "sha2"
+);
+digest_backend!(
+    /// SHA-512 truncated to 256 bits, faster than SHA-256 on 64-bit CPUs.
+    Sha512_256, sha2::Sha512_256, "sha2"
+);
+digest_backend!(
+    /// SHA3-256 as standardised in FIPS 202.
+    Sha3_256, sha3::Sha3_256, "sha3"
+);
+digest_backend!(
+    /// Keccak-256 with the original padding, as used by Ethereum.
+    Keccak256, sha3::Keccak256, "sha3"
+);
+
+/// BLAKE3 with its default 32-byte output.
+#[cfg(feature = "blake3")]
+#[derive(Clone, Debug, Default)]
+pub struct Blake3(blake3::Hasher);
+
+#[cfg(feature = "blake3")]
+impl Blake3 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(feature = "blake3")]
+impl MerkleHasher for Blake3 {
+    type Output = [u8; 32];
+
+    fn output_len(&self) -> usize {
+        blake3::OUT_LEN
+    }
+
+    fn hash(&mut self, parts: &[&[u8]]) -> [u8; 32] {
+        for part in parts {
+            self.0.update(part);
+        }
+        let out = self.0.finalize().into();
+        self.0.reset();
+        out
+    }
+}
+
+#[cfg(all(test, any(feature = "rust-crypto", feature = "sha2", feature = "sha3", feature = "blake3")))]
+mod tests {
+    use super::*;
+
+    // hashes "abc" twice, checking the hasher is reset in between
+    #[cfg(any(feature = "sha2", feature = "sha3", feature = "blake3"))]
+    fn hash_abc<H: MerkleHasher>(mut hasher: H) -> String {
+        let first = H::into_hash(hasher.hash_leaf(b"abc"));
+        assert_eq!(hasher.hash(&[b"a", b"bc"]).as_ref(), first.as_bytes());
+        first.to_hex()
+    }
+
+    #[cfg(feature = "rust-crypto")]
+    #[test]
+    fn test_rust_crypto() {
+        use crypto::sha2::Sha256;
+
+        let mut hasher = Sha256::new();
+        assert_eq!(hasher.output_len(), 32);
+
+        let pair = hasher.hash_pair(b"tea", b"coffee");
+        assert_eq!(pair, hasher.hash(&[b"teacoffee"]));
+        assert_eq!(hasher.hash_leaf(b"tea").to_hex(), "a9f74d1ec36ebdeb2da3f6e5868090cd2a2d20b3dcca7b62f60304b1d3d9ef42");
+    }
+
+    #[cfg(all(feature = "rust-crypto", feature = "digest"))]
+    #[test]
+    fn test_digest_adapter() {
+        use crypto::sha2::Sha256;
+
+        let mut hasher: DigestHasher<sha2::Sha256> = DigestHasher::new();
+        assert_eq!(hasher.output_len(), 32);
+
+        let leaf = hasher.hash_leaf(b"tea");
+        assert_eq!(leaf.as_slice(), Sha256::new().hash_leaf(b"tea").as_bytes());
+        let pair = hasher.hash_pair(b"tea", b"coffee");
+        assert_eq!(pair.as_slice(), Sha256::new().hash_pair(b"tea", b"coffee").as_bytes());
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn test_sha2() {
+        assert_eq!(hash_abc(Sha256::new()), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hash_abc(Sha512_256::new()), "53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23");
+    }
+
+    #[cfg(feature = "sha3")]
+    #[test]
+    fn test_sha3() {
+        assert_eq!(hash_abc(Sha3_256::new()), "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532");
+        assert_eq!(hash_abc(Keccak256::new()), "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45");
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn test_blake3() {
+        let mut hasher = Blake3::new();
+        assert_eq!(hasher.output_len(), 32);
+        assert_eq!(Blake3::into_hash(hasher.hash(&[])).to_hex(), "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262");
+        assert_eq!(hash_abc(hasher), "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85");
+    }
+}
@@ -0,0 +1,196 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::hash::Hash;
+
+/// Types that can be hashed as a leaf of a `MerkleTree`.
+///
+/// A leaf hashes its `leaf_bytes`. Byte strings are hashed as they are,
+/// integers as big-endian unless wrapped in `LittleEndian`. Arrays, tuples
+/// and structs deriving `Leaf` (with the `derive` feature) concatenate
+/// their fields, each written by `write_field`, so that two different
+/// values of a type never share an encoding.
+pub trait Leaf {
+    fn leaf_bytes(&self) -> Cow<'_, [u8]>;
+
+    /// Appends `self` as a field of a larger leaf: its length as a
+    /// big-endian `u64`, then its bytes. Types whose encoding has a fixed
+    /// length write the bytes alone.
+    fn write_field(&self, out: &mut Vec<u8>) {
+        let bytes = self.leaf_bytes();
+        out.extend_from_slice(&(bytes.len() as u64).to_be_bytes());
+        out.extend_from_slice(&bytes);
+    }
+}
+
+impl Leaf for str {
+    fn leaf_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.as_bytes())
+    }
+}
+
+impl Leaf for String {
+    fn leaf_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.as_bytes())
+    }
+}
+
+impl Leaf for [u8] {
+    fn leaf_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl Leaf for Vec<u8> {
+    fn leaf_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<T> Leaf for &T
+    where T: Leaf + ?Sized,
+{
+    fn leaf_bytes(&self) -> Cow<'_, [u8]> {
+        (**self).leaf_bytes()
+    }
+
+    fn write_field(&self, out: &mut Vec<u8>) {
+        (**self).write_field(out)
+    }
+}
+
+impl<T, const N: usize> Leaf for [T; N]
+    where T: Leaf,
+{
+    fn leaf_bytes(&self) -> Cow<'_, [u8]> {
+        let mut out = vec![];
+        self.write_field(&mut out);
+        Cow::Owned(out)
+    }
+
+    fn write_field(&self, out: &mut Vec<u8>) {
+        for item in self {
+            item.write_field(out);
+        }
+    }
+}
+
+/// Wraps an integer to hash it as little-endian.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct LittleEndian<T>(pub T);
+
+macro_rules! impl_leaf_for_int {
+    ($($int:ty),*) => {
+        $(
+            impl Leaf for $int {
+                fn leaf_bytes(&self) -> Cow<'_, [u8]> {
+                    Cow::Owned(self.to_be_bytes().to_vec())
+                }
+
+                fn write_field(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_be_bytes());
+                }
+            }
+
+            impl Leaf for LittleEndian<$int> {
+                fn leaf_bytes(&self) -> Cow<'_, [u8]> {
+                    Cow::Owned(self.0.to_le_bytes().to_vec())
+                }
+
+                fn write_field(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.0.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_leaf_for_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+macro_rules! impl_leaf_for_tuple {
+    ($($name:ident)+) => {
+        impl<$($name),+> Leaf for ($($name,)+)
+            where $($name: Leaf),+
+        {
+            #[allow(non_snake_case)]
+            fn leaf_bytes(&self) -> Cow<'_, [u8]> {
+                let ($($name,)+) = self;
+                let mut out = vec![];
+                $($name.write_field(&mut out);)+
+                Cow::Owned(out)
+            }
+        }
+    };
+}
+
+impl_leaf_for_tuple!(A);
+impl_leaf_for_tuple!(A B);
+impl_leaf_for_tuple!(A B C);
+impl_leaf_for_tuple!(A B C D);
+impl_leaf_for_tuple!(A B C D E);
+impl_leaf_for_tuple!(A B C D E F);
+
+/// Where a hash-only `MerkleTree` looks up the values of its leaves.
+///
+/// A value is asked for by the index of its leaf and by the leaf's hash, so
+/// that a store can be keyed by either: a slice by index, a `HashMap` by
+/// hash, which stays valid as the leaves of a sorted tree move around.
+pub trait LeafStore<T> {
+    fn value(&self, index: usize, hash: &Hash) -> Option<T>;
+}
+
+impl<T> LeafStore<T> for [T]
+    where T: Clone,
+{
+    fn value(&self, index: usize, _hash: &Hash) -> Option<T> {
+        self.get(index).cloned()
+    }
+}
+
+impl<T> LeafStore<T> for Vec<T>
+    where T: Clone,
+{
+    fn value(&self, index: usize, hash: &Hash) -> Option<T> {
+        self.as_slice().value(index, hash)
+    }
+}
+
+impl<T> LeafStore<T> for HashMap<Hash, T>
+    where T: Clone,
+{
+    fn value(&self, _index: usize, hash: &Hash) -> Option<T> {
+        self.get(hash).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes() {
+        assert_eq!(&*"tea".leaf_bytes(), b"tea");
+        assert_eq!(&*String::from("tea").leaf_bytes(), b"tea");
+        assert_eq!(&*vec![1u8, 2].leaf_bytes(), [1, 2]);
+        assert_eq!(&*[1u8, 2].leaf_bytes(), [1, 2]);
+        assert!(matches!("tea".leaf_bytes(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(&*1u32.leaf_bytes(), [0, 0, 0, 1]);
+        assert_eq!(&*LittleEndian(1u32).leaf_bytes(), [1, 0, 0, 0]);
+        assert_eq!(&*(-1i16).leaf_bytes(), [0xff, 0xff]);
+        assert_eq!(&*[1u16, 2].leaf_bytes(), [0, 1, 0, 2]);
+    }
+
+    #[test]
+    fn test_tuples() {
+        assert_eq!(&*(1u8, 2u16).leaf_bytes(), [1, 0, 2]);
+        assert_eq!(&*("ab", 1u8).leaf_bytes(), [0, 0, 0, 0, 0, 0, 0, 2, b'a', b'b', 1]);
+
+        // the length prefix keeps the boundary between fields
+        assert_ne!(("ab", "c").leaf_bytes(), ("a", "bc").leaf_bytes());
+        assert_ne!(((1u8, 2u8), 3u8).leaf_bytes(), (1u8, (2u8, 3u8)).leaf_bytes());
+    }
+}
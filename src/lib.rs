@@ -1 +1,39 @@
+#[cfg(feature = "rust-crypto")]
+mod bitcoin;
+mod hash;
+mod hasher;
+mod leaf;
 mod merkle_tree;
+mod padding;
+mod patricia_trie;
+#[cfg(feature = "poseidon")]
+mod poseidon;
+mod proof;
+mod rlp;
+mod sparse_merkle_tree;
+mod streaming_root;
+
+#[cfg(feature = "rust-crypto")]
+pub use crate::bitcoin::{bitcoin_merkle_root, DoubleSha256, MerkleBlock, PartialMerkleTree};
+pub use crate::hash::{Hash, HashScheme};
+#[cfg(feature = "blake3")]
+pub use crate::hasher::Blake3;
+#[cfg(feature = "digest")]
+pub use crate::hasher::DigestHasher;
+#[cfg(feature = "sha3")]
+pub use crate::hasher::{Keccak256, Sha3_256};
+#[cfg(feature = "sha2")]
+pub use crate::hasher::{Sha256, Sha512_256};
+pub use crate::hasher::MerkleHasher;
+pub use crate::leaf::{Leaf, LeafStore, LittleEndian};
+#[cfg(feature = "derive")]
+pub use merkle_tree_derive::Leaf;
+pub use crate::merkle_tree::{Config, LeafOrder, MerkleTree, MerkleTreeBuilder, Node, Removal};
+pub use crate::padding::PaddingStrategy;
+pub use crate::patricia_trie::PatriciaTrie;
+#[cfg(feature = "poseidon")]
+pub use crate::poseidon::{FieldElement, Poseidon};
+pub use crate::proof::{ConsistencyProof, KaryProof, MerkleProof, MultiProof, NonMembershipProof, PatriciaProof, Position, SparseMerkleProof};
+pub use crate::rlp::Rlp;
+pub use crate::sparse_merkle_tree::{SparseMerkleTree, KEY_BITS};
+pub use crate::streaming_root::StreamingRoot;
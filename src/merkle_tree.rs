@@ -1,224 +1,1253 @@
-extern crate crypto;
+use std::borrow::Cow;
+use std::ops::{Index, IndexMut};
 
-use crypto::digest::Digest;
+use crate::hash::{Hash, HashScheme};
+use crate::hasher::MerkleHasher;
+use crate::leaf::{Leaf, LeafStore};
+use crate::padding::{self, PaddingStrategy};
+use crate::proof::{ConsistencyProof, KaryProof, MerkleProof, MultiProof, NonMembershipProof, Position};
 
-pub trait AsBytes {
-    fn as_bytes(&self) -> &[u8];
+/// A node of a `MerkleTree`. Leaves carry the value they were built
+/// from, unless the tree is hash-only; internal nodes only carry a hash.
+#[derive(Clone, Debug)]
+pub struct Node<T>
+    where T: Leaf + Clone,
+{
+    value: Option<T>,
+    hash: Hash,
+    leaf: bool,
+}
+
+impl<T> Node<T>
+    where T: Leaf + Clone,
+{
+    pub fn value(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    pub fn hash(&self) -> &Hash {
+        &self.hash
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.leaf
+    }
+
+    // fills the unused room of `Levels`, without allocating
+    fn vacant() -> Self {
+        Node {
+            value: None,
+            hash: Hash::new(&[]),
+            leaf: false,
+        }
+    }
+}
+
+/// Options controlling how a `MerkleTree` is built.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub scheme: HashScheme,
+    pub padding: PaddingStrategy,
+    pub order: LeafOrder,
+    /// Number of children of each internal node. Trees of arity above two
+    /// are shallower, and must pad with `DuplicateLast` or `Zero`.
+    pub arity: usize,
+    /// Keep only the hashes of the leaves, dropping each value once it is
+    /// hashed. Values are then looked up in a `LeafStore`, see
+    /// `MerkleTree::value`. Hash-only trees cannot be sorted `ByValue`.
+    pub hash_only: bool,
 }
 
-impl AsBytes for str {
-    fn as_bytes(&self) -> &[u8] {
-        str::as_bytes(self)
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            scheme: HashScheme::default(),
+            padding: PaddingStrategy::default(),
+            order: LeafOrder::default(),
+            arity: 2,
+            hash_only: false,
+        }
     }
 }
 
-impl AsBytes for String {
-    fn as_bytes(&self) -> &[u8] {
-        String::as_bytes(self)
+/// The order the leaves of a `MerkleTree` are kept in. Sorted trees can
+/// prove that a value is absent, see `MerkleTree::non_membership_proof`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LeafOrder {
+    /// The order the leaves were given or appended in.
+    #[default]
+    Insertion,
+    /// Sorted by leaf hash.
+    ByHash,
+    /// Sorted by the leaf's bytes.
+    ByValue,
+}
+
+impl LeafOrder {
+    pub fn is_sorted(self) -> bool {
+        self != LeafOrder::Insertion
+    }
+
+    /// The bytes a leaf is sorted by, given its value and hash.
+    pub(crate) fn key<'a, T>(self, value: &'a T, hash: &'a Hash) -> Cow<'a, [u8]>
+        where T: Leaf + ?Sized,
+    {
+        match self {
+            LeafOrder::ByHash => Cow::Borrowed(hash.as_bytes()),
+            LeafOrder::Insertion | LeafOrder::ByValue => value.leaf_bytes(),
+        }
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct Node<T>
-    where T: AsBytes + Clone,
-{
-    value: Option<T>,
-    hash: String,
+impl Config {
+    /// The Certificate Transparency Merkle Tree Hash of RFC 6962 (and RFC
+    /// 9162): domain-separated hashing over an unbalanced tree, which splits
+    /// at the largest power of two smaller than the number of leaves. Use it
+    /// with SHA-256 to match CT logs.
+    pub fn rfc6962() -> Self {
+        Config {
+            scheme: HashScheme::DomainSeparated,
+            padding: PaddingStrategy::Unbalanced,
+            order: LeafOrder::Insertion,
+            arity: 2,
+            hash_only: false,
+        }
+    }
+
+    /// Bitcoin's transaction merkle tree: the last node of every odd level
+    /// is paired with itself. Use it with `DoubleSha256` over raw
+    /// transactions, or see `bitcoin_merkle_root` to start from txids.
+    pub fn bitcoin() -> Self {
+        Config {
+            scheme: HashScheme::Unprefixed,
+            padding: PaddingStrategy::DuplicateOdd,
+            order: LeafOrder::Insertion,
+            arity: 2,
+            hash_only: false,
+        }
+    }
+
+    pub(crate) fn check(self) -> Result<(), &'static str> {
+        if self.arity < 2 {
+            return Err("Arity must be at least two");
+        }
+        if self.arity > 2 && !self.padding.pads_to_power_of_two() {
+            return Err("Padding strategy requires a binary tree");
+        }
+        if self.hash_only && self.order == LeafOrder::ByValue {
+            return Err("Hash-only trees cannot be sorted by value");
+        }
+        Ok(())
+    }
+}
+
+/// How `MerkleTree::remove_leaf` fills the gap left by a removed leaf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Removal {
+    /// Move the last leaf into the gap, like `Vec::swap_remove`.
+    Swap,
+    /// Shift the following leaves one place left, like `Vec::remove`.
+    Shift,
 }
 
+/// A merkle tree, binary unless configured otherwise, stored level by
+/// level from the leaves up to the root, all in one buffer. Only nodes
+/// covering at least one real leaf are stored; padding is resolved on the
+/// fly according to the tree's `PaddingStrategy`.
 pub struct MerkleTree<H, T>
-    where H: Digest,
-          T: AsBytes + Clone,
+    where H: MerkleHasher,
+          T: Leaf + Clone,
 {
     hasher: H,
-    nodes: Vec<Node<T>>,
-    leaves: usize,
+    config: Config,
+    levels: Levels<T>,
+    // hash of a fully padded subtree at each level, for the strategies
+    // that pad up to a power of two
+    padding: Vec<Hash>,
 }
 
 impl<H, T> MerkleTree<H, T>
-    where H: Digest,
-          T: AsBytes + Clone,
+    where H: MerkleHasher,
+          T: Leaf + Clone,
 {
-    fn root(&self) -> Result<&Node<T>, &'static str> {
-        match self.nodes.as_slice().last() {
+    /// Builds a tree over `values`, padding them with copies of the last
+    /// value up to the next power of two.
+    pub fn from_leaves(values: &[T], hasher: H) -> Result<Self, &'static str> {
+        Self::from_leaves_with_config(values, hasher, Config::default())
+    }
+
+    pub fn from_leaves_with_config(values: &[T], hasher: H, config: Config) -> Result<Self, &'static str> {
+        let mut builder = MerkleTreeBuilder::new(hasher, config)?;
+        builder.leaves.reserve(values.len());
+        for v in values {
+            builder.push_ref(v)?;
+        }
+        builder.build()
+    }
+
+    /// Builds a tree over the values `values` yields, taking ownership of
+    /// them instead of cloning, see `MerkleTreeBuilder`.
+    pub fn from_iter<I>(values: I, hasher: H) -> Result<Self, &'static str>
+        where I: IntoIterator<Item = T>,
+    {
+        Self::from_iter_with_config(values, hasher, Config::default())
+    }
+
+    pub fn from_iter_with_config<I>(values: I, hasher: H, config: Config) -> Result<Self, &'static str>
+        where I: IntoIterator<Item = T>,
+    {
+        let mut builder = MerkleTreeBuilder::new(hasher, config)?;
+        builder.extend(values);
+        builder.build()
+    }
+
+    /// Builds a hash-only tree over leaves that were hashed beforehand, with
+    /// `config.scheme.hash_leaf`. The tree's config has `hash_only` set.
+    pub fn from_hashes(hashes: &[Hash], hasher: H, config: Config) -> Result<Self, &'static str> {
+        if hashes.is_empty() {
+            return Err("Leaves cannot be empty");
+        }
+        let config = Config { hash_only: true, ..config };
+        config.check()?;
+
+        let leaf_nodes = hashes.iter()
+            .map(|hash| Self::hashed_leaf_node(hash.clone(), &hasher))
+            .collect::<Result<Vec<Node<T>>, &'static str>>()?;
+
+        Ok(Self::from_leaf_nodes(leaf_nodes, hasher, config))
+    }
+
+    fn from_leaf_nodes(leaf_nodes: Vec<Node<T>>, hasher: H, config: Config) -> Self {
+        Self::from_leaf_nodes_with(leaf_nodes, hasher, config, Self::build_parent_nodes)
+    }
+
+    // `build_parent_nodes` hashes each level from the one below
+    fn from_leaf_nodes_with<F>(mut leaf_nodes: Vec<Node<T>>, hasher: H, config: Config, build_parent_nodes: F) -> Self
+        where F: FnMut(&[Node<T>], &mut [Node<T>], Option<&Hash>, Config, &mut H),
+    {
+        if config.order.is_sorted() {
+            leaf_nodes.sort_by_cached_key(|node| Self::sort_key(config.order, node).into_owned());
+        }
+
+        let mut mt = MerkleTree {
+            hasher,
+            config,
+            levels: Levels::default(),
+            padding: vec![],
+        };
+        mt.build(leaf_nodes, build_parent_nodes);
+
+        mt
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn root(&self) -> Result<&Node<T>, &'static str> {
+        match self.levels.last().and_then(|level| level.first()) {
             Some(root) => Ok(root),
             None => Err("Error constructing merkle tree")
         }
     }
 
-    fn count_leaves(&self) -> Result<usize, &'static str> {
-        if (self.nodes.len() + 1usize).is_power_of_two() {
-            Ok((self.nodes.len() + 1usize) / 2usize)
-        } else {
+    pub fn root_hash(&self) -> Result<&Hash, &'static str> {
+        self.root().map(Node::hash)
+    }
+
+    /// Number of leaves the tree was built from, excluding padding.
+    pub fn len(&self) -> usize {
+        self.levels.first().map_or(0, <[_]>::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of leaves in the tree, including padding.
+    pub fn count_leaves(&self) -> Result<usize, &'static str> {
+        if self.is_empty() {
             Err("Merkle tree has not been constructed correctly")
+        } else if self.config.arity > 2 {
+            Ok(self.config.arity.pow(self.levels.len() as u32 - 1))
+        } else {
+            Ok(self.config.padding.padded_len(self.len()))
         }
     }
 
-    // new leaves will be replace the ones that
-    // were duplicated just to make leaves == 2^n
-    fn add_leaves(&mut self, values: &mut Vec<T>) {
-        let count_new_leaves = self.leaves + values.len();
+    pub fn leaf(&self, index: usize) -> Option<&Node<T>> {
+        self.levels.first().and_then(|leaves| leaves.get(index))
+    }
+
+    /// Iterates over the leaves the tree was built from, excluding padding.
+    pub fn leaves(&self) -> std::slice::Iter<'_, Node<T>> {
+        self.levels[0].iter()
+    }
+
+    /// The value of the leaf at `index`. A hash-only tree looks it up in
+    /// `store`, and checks that it hashes to the leaf.
+    pub fn value<S>(&self, index: usize, store: &S) -> Result<T, &'static str>
+        where H: Clone,
+              S: LeafStore<T> + ?Sized,
+    {
+        let leaf = self.leaf(index).ok_or("Leaf index out of bounds")?;
+        if let Some(value) = &leaf.value {
+            return Ok(value.clone());
+        }
+
+        let value = store.value(index, &leaf.hash).ok_or("Value missing from the store")?;
+        if self.config.scheme.try_hash_leaf(&value, &mut self.hasher.clone())? != self.levels[0][index].hash {
+            return Err("Stored value does not match the leaf");
+        }
+        Ok(value)
+    }
 
-        let n = count_new_leaves.next_power_of_two();
-        if count_new_leaves < n {
-            let pad_by = n - count_new_leaves;
-            if let Some(last) = values.last().map(|v| (*v).clone()) {
-                let extend_by = vec![last; pad_by];
-                values.extend(extend_by);
+    /// Builds an inclusion proof for the leaf at `index` of a binary tree,
+    /// see `kary_proof` for other arities.
+    pub fn proof(&self, index: usize) -> Result<MerkleProof, &'static str> {
+        if index >= self.len() {
+            return Err("Leaf index out of bounds");
+        }
+        if self.config.arity != 2 {
+            return Err("Tree is not binary");
+        }
+
+        let mut path = vec![];
+        let mut i = index;
+        for (level, nodes) in self.levels.iter().take(self.levels.len() - 1).enumerate() {
+            let position = if i & 1 == 0 { Position::Right } else { Position::Left };
+            match nodes.get(i ^ 1) {
+                Some(sibling) => path.push((position, sibling.hash.clone())),
+                None => {
+                    if let Some(sibling) = self.missing_sibling(level, &nodes[i].hash) {
+                        path.push((Position::Right, sibling));
+                    }
+                }
             }
+            i /= 2;
         }
-        let mut new_leaf_nodes = vec![];
-        for v in values {
-            let leaf_node: Node<T> = Self::as_leaf(v, &mut self.hasher);
-            new_leaf_nodes.push(leaf_node);
+
+        Ok(MerkleProof::new(index, path))
+    }
+
+    /// Builds an inclusion proof for the leaf at `index` of a tree of any
+    /// arity, carrying all the siblings of each node on the path, padding
+    /// included.
+    pub fn kary_proof(&self, index: usize) -> Result<KaryProof, &'static str> {
+        if index >= self.len() {
+            return Err("Leaf index out of bounds");
         }
 
-        let mut nodes = vec![];
-        self.nodes.truncate(self.leaves);
-        nodes.extend_from_slice(self.nodes.as_slice());
-        nodes.extend(new_leaf_nodes);
+        let arity = self.config.arity;
+        let mut path = vec![];
+        let mut i = index;
+        for (level, nodes) in self.levels.iter().take(self.levels.len() - 1).enumerate() {
+            let first = i - i % arity;
+            let last = (first + arity).min(nodes.len());
+            let mut siblings: Vec<Hash> = nodes[first..last].iter()
+                .enumerate()
+                .filter(|&(k, _)| first + k != i)
+                .map(|(_, node)| node.hash.clone())
+                .collect();
+            if last - first < arity {
+                if let Some(sibling) = self.missing_sibling(level, &nodes[last - 1].hash) {
+                    siblings.resize(arity - 1, sibling);
+                }
+            }
+            path.push((i - first, siblings));
+            i /= arity;
+        }
+
+        Ok(KaryProof::new(index, path))
+    }
 
-        let parent_nodes: Vec<Node<T>> = Self::build_parent_nodes(&nodes, &mut self.hasher);
+    /// Builds a single proof for several leaves at once. Sibling hashes
+    /// shared between the leaves' paths, or computable from the leaves
+    /// themselves, are only included once or not at all.
+    pub fn multi_proof(&self, indices: &[usize]) -> Result<MultiProof, &'static str> {
+        if indices.is_empty() {
+            return Err("Indices cannot be empty");
+        }
+        if self.config.arity != 2 {
+            return Err("Tree is not binary");
+        }
 
-        nodes.extend(parent_nodes);
+        let mut known = indices.to_vec();
+        known.sort_unstable();
+        known.dedup();
+        if known.iter().any(|&i| i >= self.len()) {
+            return Err("Leaf index out of bounds");
+        }
 
-        self.leaves = count_new_leaves;
-        self.nodes = nodes;
+        let proof_indices = known.clone();
+        let mut hashes = vec![];
+        for (level, nodes) in self.levels.iter().take(self.levels.len() - 1).enumerate() {
+            let mut parents = vec![];
+            let mut k = 0;
+            while k < known.len() {
+                let i = known[k];
+                if i & 1 == 0 && known.get(k + 1) == Some(&(i + 1)) {
+                    k += 2;
+                } else {
+                    match nodes.get(i ^ 1) {
+                        Some(sibling) => hashes.push(sibling.hash.clone()),
+                        // the verifier can't derive padding hashes by itself
+                        None if self.config.padding.pads_to_power_of_two() => {
+                            hashes.push(self.padding[level].clone());
+                        }
+                        None => {}
+                    }
+                    k += 1;
+                }
+                parents.push(i / 2);
+            }
+
+            known = parents;
+        }
+
+        Ok(MultiProof::new(self.len(), self.config.padding, proof_indices, hashes))
+    }
+
+    /// Builds a proof that the tree over the first `new_size` leaves is an
+    /// append-only extension of the tree over the first `old_size` leaves
+    /// (RFC 9162, section 2.1.4). Only unbalanced trees keep the subtrees of
+    /// their earlier sizes as they grow, so other padding strategies are
+    /// rejected.
+    pub fn consistency_proof(&self, old_size: usize, new_size: usize) -> Result<ConsistencyProof, &'static str>
+        where H: Clone,
+    {
+        if self.config.padding != PaddingStrategy::Unbalanced {
+            return Err("Consistency proofs require an unbalanced tree");
+        }
+        if old_size == 0 || old_size > new_size || new_size > self.len() {
+            return Err("Tree size out of bounds");
+        }
+
+        let mut hashes = vec![];
+        self.subproof(old_size, 0, new_size, true, &mut hashes, &mut self.hasher.clone());
+
+        Ok(ConsistencyProof::new(old_size, new_size, hashes))
+    }
+
+    /// Proves that `value` is not a leaf of a sorted tree, with inclusion
+    /// proofs for the leaves just before and after where it would sit.
+    /// Hash-only trees need `non_membership_proof_with_store`.
+    pub fn non_membership_proof(&self, value: &T) -> Result<NonMembershipProof, &'static str>
+        where H: Clone,
+    {
+        self.non_membership_proof_with_store(value, &[][..])
+    }
+
+    /// `non_membership_proof` for a hash-only tree, which looks up the
+    /// values of the neighbouring leaves in `store`.
+    pub fn non_membership_proof_with_store<S>(&self, value: &T, store: &S) -> Result<NonMembershipProof, &'static str>
+        where H: Clone,
+              S: LeafStore<T> + ?Sized,
+    {
+        let order = self.config.order;
+        if !order.is_sorted() {
+            return Err("Leaves are not sorted");
+        }
+
+        let hash = self.config.scheme.try_hash_leaf(value, &mut self.hasher.clone())?;
+        let key = order.key(value, &hash);
+        let index = self.levels[0].partition_point(|node| Self::sort_key(order, node) < key);
+        if self.levels[0].get(index).map(|node| Self::sort_key(order, node)) == Some(key) {
+            return Err("Value is a leaf of the tree");
+        }
+
+        let neighbour = |i: usize| -> Result<(Vec<u8>, MerkleProof), &'static str> {
+            let bytes = self.value(i, store)?.leaf_bytes().into_owned();
+            Ok((bytes, self.proof(i)?))
+        };
+        let left = if index > 0 { Some(neighbour(index - 1)?) } else { None };
+        let right = if index < self.len() { Some(neighbour(index)?) } else { None };
+
+        Ok(NonMembershipProof::new(self.len(), self.config.padding, order, left, right))
+    }
+
+    // SUBPROOF(m, D[start:end], b) from RFC 9162
+    fn subproof(&self, m: usize, start: usize, end: usize, complete: bool, hashes: &mut Vec<Hash>, hasher: &mut H) {
+        if start + m == end {
+            if !complete {
+                hashes.push(self.subtree_hash(start, end, hasher));
+            }
+            return;
+        }
+
+        let k = padding::split(end - start);
+        if m <= k {
+            self.subproof(m, start, start + k, complete, hashes, hasher);
+            hashes.push(self.subtree_hash(start + k, end, hasher));
+        } else {
+            self.subproof(m - k, start + k, end, false, hashes, hasher);
+            hashes.push(self.subtree_hash(start, start + k, hasher));
+        }
+    }
+
+    // hash of the unbalanced subtree over leaves[start..end], which is
+    // stored unless `end` falls short of the node covering `start`
+    fn subtree_hash(&self, start: usize, end: usize, hasher: &mut H) -> Hash {
+        let level = padding::depth(end - start, 2) - 1;
+        let index = start >> level;
+        if index << level == start && ((index + 1) << level).min(self.len()) == end {
+            return self.levels[level][index].hash.clone();
+        }
+
+        let k = padding::split(end - start);
+        let left = self.subtree_hash(start, start + k, hasher);
+        let right = self.subtree_hash(start + k, end, hasher);
+        self.config.scheme.hash_internal(&left, &right, hasher)
+    }
+
+    /// Appends `values` to the leaves, one at a time, see `add_leaf`. When
+    /// the hasher rejects one of them, none is added.
+    pub fn add_leaves(&mut self, values: &[T]) -> Result<(), &'static str> {
+        let leaf_nodes = values.iter()
+            .map(|v| Self::leaf_node(v, self.config, &mut self.hasher))
+            .collect::<Result<Vec<Node<T>>, &'static str>>()?;
+        for leaf_node in leaf_nodes {
+            self.insert_leaf(leaf_node);
+        }
+        Ok(())
+    }
+
+    /// Appends `value` to the leaves. Only the last node of each level can
+    /// change, so this hashes O(log n) nodes; a new root level is added when
+    /// the top level outgrows a single node. A sorted tree inserts `value`
+    /// in order instead, rehashing everything to its right.
+    pub fn add_leaf(&mut self, value: &T) -> Result<(), &'static str> {
+        let leaf_node: Node<T> = Self::leaf_node(value, self.config, &mut self.hasher)?;
+        self.insert_leaf(leaf_node);
+        Ok(())
+    }
+
+    /// Appends a leaf hashed beforehand, see `from_hashes`. The tree must
+    /// be hash-only.
+    pub fn add_leaf_hash(&mut self, hash: Hash) -> Result<(), &'static str> {
+        if !self.config.hash_only {
+            return Err("Tree is not hash-only");
+        }
+        let leaf_node = Self::hashed_leaf_node(hash, &self.hasher)?;
+        self.insert_leaf(leaf_node);
+        Ok(())
+    }
+
+    fn insert_leaf(&mut self, leaf_node: Node<T>) {
+        let order = self.config.order;
+        let index = if order.is_sorted() {
+            let key = Self::sort_key(order, &leaf_node);
+            self.levels[0].partition_point(|node| Self::sort_key(order, node) <= key)
+        } else {
+            self.len()
+        };
+
+        self.levels.insert_leaf(index, leaf_node);
+        self.refresh_padding();
+        self.rebuild_from(index);
     }
 
-    fn from_leaves(values: &mut Vec<T>, mut hasher: H) -> Result<Self, &'static str> {
-        if values.len() == 0 {
+    /// Removes the leaf at `index` and returns it. `Removal::Swap` moves the
+    /// last leaf into its place, which rehashes O(log n) nodes;
+    /// `Removal::Shift` keeps the order of the remaining leaves and rehashes
+    /// every node to the right of `index`. The last leaf cannot be removed,
+    /// and sorted trees only allow `Removal::Shift`.
+    pub fn remove_leaf(&mut self, index: usize, removal: Removal) -> Result<Node<T>, &'static str> {
+        if index >= self.len() {
+            return Err("Leaf index out of bounds");
+        }
+        if self.len() == 1 {
             return Err("Leaves cannot be empty");
         }
+        if removal == Removal::Swap && self.config.order.is_sorted() {
+            return Err("Swap removal would unsort the leaves");
+        }
 
-        let leaves = values.len();
-        let n = values.len().next_power_of_two();
-        if values.len() < n {
-            let pad_by = n - values.len();
-            if let Some(last) = values.last().map(|v| (*v).clone()) {
-                let extend_by = vec![last; pad_by];
-                values.extend(extend_by);
+        let removed = match removal {
+            Removal::Swap => {
+                let removed = self.levels.swap_remove_leaf(index);
+                self.refresh_padding();
+                self.rebuild_from(self.len());
+                if index < self.len() {
+                    self.rehash_ancestors(vec![index]);
+                }
+                removed
             }
+            Removal::Shift => {
+                let removed = self.levels.remove_leaf(index);
+                self.refresh_padding();
+                self.rebuild_from(index);
+                removed
+            }
+        };
+
+        Ok(removed)
+    }
+
+    /// Keeps the first `len` leaves and drops the rest, along with the
+    /// levels a tree over `len` leaves no longer needs. Does nothing when
+    /// `len` is not smaller than the number of leaves.
+    pub fn truncate(&mut self, len: usize) -> Result<(), &'static str> {
+        if len == 0 {
+            return Err("Leaves cannot be empty");
+        }
+        if len >= self.len() {
+            return Ok(());
         }
 
-        let mut nodes: Vec<Node<T>> = vec![];
-        for v in values {
-            let leaf_node: Node<T> = Self::as_leaf(v, &mut hasher);
-            nodes.push(leaf_node);
+        self.levels.truncate_leaves(len);
+        self.refresh_padding();
+        self.rebuild_from(len);
+
+        Ok(())
+    }
+
+    /// Replaces the leaf at `index` with `value` and rehashes its ancestors,
+    /// returning the new root hash.
+    pub fn update_leaf(&mut self, index: usize, value: &T) -> Result<&Hash, &'static str> {
+        self.update_leaves(&[(index, value.clone())])
+    }
+
+    /// Replaces several leaves at once, given as `(index, value)` pairs, and
+    /// rehashes each of their common ancestors once. When an index repeats,
+    /// the last value wins. Returns the new root hash. Sorted trees keep
+    /// their leaves in order, so they cannot be updated in place.
+    pub fn update_leaves(&mut self, updates: &[(usize, T)]) -> Result<&Hash, &'static str> {
+        self.check_updates(updates.iter().map(|(i, _)| i))?;
+
+        // hash every value before replacing any, so that a rejected one
+        // leaves the tree as it was
+        let leaf_nodes = updates.iter()
+            .map(|(_, v)| Self::leaf_node(v, self.config, &mut self.hasher))
+            .collect::<Result<Vec<Node<T>>, &'static str>>()?;
+        let mut dirty = Vec::with_capacity(updates.len());
+        for ((i, _), leaf_node) in updates.iter().zip(leaf_nodes) {
+            self.levels[0][*i] = leaf_node;
+            dirty.push(*i);
         }
+        self.rehash_updated(dirty)
+    }
 
-        let parent_nodes: Vec<Node<T>> = Self::build_parent_nodes(&nodes, &mut hasher);
+    /// Replaces the leaf at `index` of a hash-only tree with a leaf hashed
+    /// beforehand, and rehashes its ancestors, returning the new root hash.
+    pub fn update_leaf_hash(&mut self, index: usize, hash: Hash) -> Result<&Hash, &'static str> {
+        if !self.config.hash_only {
+            return Err("Tree is not hash-only");
+        }
+        self.check_updates(std::iter::once(&index))?;
 
-        nodes.extend(parent_nodes);
+        self.levels[0][index] = Self::hashed_leaf_node(hash, &self.hasher)?;
+        self.rehash_updated(vec![index])
+    }
 
-        Ok(MerkleTree {
-            hasher: hasher,
-            nodes: nodes,
-            leaves: leaves
-        })
+    fn check_updates<'a>(&self, mut indices: impl Iterator<Item = &'a usize>) -> Result<(), &'static str> {
+        if self.config.order.is_sorted() {
+            return Err("Leaves of a sorted tree cannot be updated in place");
+        }
+        if indices.any(|&i| i >= self.len()) {
+            return Err("Leaf index out of bounds");
+        }
+        Ok(())
+    }
+
+    fn rehash_updated(&mut self, mut dirty: Vec<usize>) -> Result<&Hash, &'static str> {
+        dirty.sort_unstable();
+        dirty.dedup();
+
+        if dirty.last() == Some(&(self.len() - 1)) {
+            self.refresh_padding();
+        }
+        self.rehash_ancestors(dirty);
+
+        self.root_hash()
     }
 
-    fn build_parent_nodes(children: &Vec<Node<T>>, mut hasher: &mut H) -> Vec<Node<T>> {
-        let mut parent_nodes = vec![];
+    // rehashes the ancestors of the leaves at `dirty`, sorted and deduped,
+    // each of them once
+    fn rehash_ancestors(&mut self, mut dirty: Vec<usize>) {
+        let arity = self.config.arity;
+        for level in 0..self.levels.len() - 1 {
+            let mut parents: Vec<usize> = dirty.iter().map(|i| i / arity).collect();
+            parents.dedup();
+
+            for &p in &parents {
+                let children = &self.levels[level];
+                let group = &children[p * arity..(p * arity + arity).min(children.len())];
+                let parent = Self::parent_node(group, self.padding.get(level), self.config, &mut self.hasher);
+                self.levels[level + 1][p] = parent;
+            }
+            dirty = parents;
+        }
+    }
 
-        for pairs in children.iter().collect::<Vec<_>>().chunks(2) {
-            let left_child = pairs[0];
-            let right_child = pairs[1];
+    // rehashes every node covering a leaf at or after `index`, growing or
+    // shrinking the levels to fit the current number of leaves
+    fn rebuild_from(&mut self, index: usize) {
+        let arity = self.config.arity;
+        let mut level = 0;
+        let mut index = index;
+        while self.levels[level].len() > 1 {
+            let start = index - index % arity;
+            let width = self.levels[level].len().div_ceil(arity);
+            self.levels.resize(level + 1, width);
 
-            parent_nodes.push(Self::as_internal(&left_child, &right_child, &mut hasher));
+            let (children, parents) = self.levels.split_at_mut(level);
+            Self::build_parent_nodes(
+                &children[start..],
+                &mut parents[start / arity..],
+                self.padding.get(level),
+                self.config,
+                &mut self.hasher,
+            );
+            index /= arity;
+            level += 1;
         }
+        self.levels.truncate(level + 1);
+    }
+
+    // lays out every level in one buffer sized for `leaf_nodes`, then
+    // hashes each level from the one below, leaves up
+    fn build<F>(&mut self, leaf_nodes: Vec<Node<T>>, mut build_parent_nodes: F)
+        where F: FnMut(&[Node<T>], &mut [Node<T>], Option<&Hash>, Config, &mut H),
+    {
+        self.levels = Levels::new(leaf_nodes, self.config.arity);
+        self.refresh_padding();
+
+        for level in 0..self.levels.len() - 1 {
+            let (children, parents) = self.levels.split_at_mut(level);
+            build_parent_nodes(children, parents, self.padding.get(level), self.config, &mut self.hasher);
+        }
+    }
+
+    // the padding hashes depend on the number of leaves and, when
+    // duplicating, on the last leaf
+    fn refresh_padding(&mut self) {
+        let leaves = &self.levels[0];
+        let depth = padding::depth(leaves.len(), self.config.arity);
+        let last_leaf = &leaves[leaves.len() - 1].hash;
+        self.padding = self.config.padding.padding_hashes(last_leaf, depth, self.config.arity, self.config.scheme, &mut self.hasher);
+    }
+
+    fn missing_sibling(&self, level: usize, node: &Hash) -> Option<Hash> {
+        self.config.padding.missing_sibling(node, self.padding.get(level))
+    }
 
-        if parent_nodes.len() > 1 {
-            let new_parents: Vec<Node<T>> = Self::build_parent_nodes(&parent_nodes, &mut hasher);
-            parent_nodes.extend(new_parents);
-            return parent_nodes;
+    // writes the parents of `children` into `parents`, in order
+    fn build_parent_nodes(children: &[Node<T>], parents: &mut [Node<T>], padding: Option<&Hash>, config: Config, hasher: &mut H) {
+        for (group, parent) in children.chunks(config.arity).zip(parents) {
+            *parent = Self::parent_node(group, padding, config, hasher);
+        }
+    }
+
+    // parent of the nodes in `group`, followed by whatever the padding
+    // strategy puts in place of the missing children of a short group
+    fn parent_node(group: &[Node<T>], padding: Option<&Hash>, config: Config, hasher: &mut H) -> Node<T> {
+        if config.arity == 2 && group.len() == 2 {
+            return Self::as_internal(&group[0], &group[1], config.scheme, hasher);
+        }
+
+        let missing = if group.len() < config.arity {
+            config.padding.missing_sibling(&group[group.len() - 1].hash, padding)
+        } else {
+            None
+        };
+        let mut children: Vec<&Hash> = group.iter().map(|node| &node.hash).collect();
+        if let Some(sibling) = &missing {
+            children.resize(config.arity, sibling);
+        }
+        let hash = match children.len() {
+            1 => children[0].clone(),
+            _ => config.scheme.hash_children(&children, hasher),
+        };
+
+        Node {
+            value: None,
+            hash,
+            leaf: false,
+        }
+    }
+
+    // the bytes `node` is sorted by
+    fn sort_key(order: LeafOrder, node: &Node<T>) -> Cow<'_, [u8]> {
+        match &node.value {
+            Some(v) => order.key(v, &node.hash),
+            None => Cow::Borrowed(node.hash.as_bytes()),
+        }
+    }
+
+    // a hash-only tree drops the value once it is hashed
+    fn leaf_node(v: &T, config: Config, hasher: &mut H) -> Result<Node<T>, &'static str> {
+        if config.hash_only {
+            Ok(Self::as_hashed_leaf(config.scheme.try_hash_leaf(v, hasher)?))
         } else {
-            return parent_nodes;
+            Self::as_leaf(v, config.scheme, hasher)
         }
     }
 
-    fn as_leaf(v: &T, hasher: &mut H) -> Node<T> {
-        hasher.reset();
-        hasher.input(v.as_bytes());
-        let hash = hasher.result_str();
+    // `leaf_node` for a value the tree can keep without cloning
+    fn owned_leaf_node(v: T, config: Config, hasher: &mut H) -> Result<Node<T>, &'static str> {
+        let hash = config.scheme.try_hash_leaf(&v, hasher)?;
+
+        Ok(Node {
+            value: if config.hash_only { None } else { Some(v) },
+            hash,
+            leaf: true,
+        })
+    }
+
+    // a leaf hashed by the caller, which the hasher must accept as input
+    fn hashed_leaf_node(hash: Hash, hasher: &H) -> Result<Node<T>, &'static str> {
+        hasher.check_input(hash.as_bytes())?;
+        Ok(Self::as_hashed_leaf(hash))
+    }
+
+    fn as_leaf(v: &T, scheme: HashScheme, hasher: &mut H) -> Result<Node<T>, &'static str> {
+        let hash = scheme.try_hash_leaf(v, hasher)?;
 
         let value = v.clone();
 
-        Node {
+        Ok(Node {
             value: Some(value),
-            hash: hash,
+            hash,
+            leaf: true,
+        })
+    }
+
+    fn as_hashed_leaf(hash: Hash) -> Node<T> {
+        Node {
+            value: None,
+            hash,
+            leaf: true,
         }
     }
 
-    fn as_internal(left: &Node<T>, right: &Node<T>, hasher: &mut H) -> Node<T> {
-        hasher.reset();
-        hasher.input(left.hash.as_bytes());
-        hasher.input(right.hash.as_bytes());
-        let hash = hasher.result_str();
+    fn as_internal(left: &Node<T>, right: &Node<T>, scheme: HashScheme, hasher: &mut H) -> Node<T> {
+        let hash = scheme.hash_internal(&left.hash, &right.hash, hasher);
 
         Node {
             value: None,
-            hash: hash,
+            hash,
+            leaf: false,
+        }
+    }
+}
+
+/// Construction spreading the hashing over the rayon thread pool, with the
+/// `parallel` feature. Each worker hashes with its own clone of the hasher.
+/// The tree is the same as the sequential constructors build.
+#[cfg(feature = "parallel")]
+impl<H, T> MerkleTree<H, T>
+    where H: MerkleHasher + Clone + Send + Sync,
+          T: Leaf + Clone + Send + Sync,
+{
+    pub fn par_from_leaves(values: &[T], hasher: H) -> Result<Self, &'static str> {
+        Self::par_from_leaves_with_config(values, hasher, Config::default())
+    }
+
+    pub fn par_from_leaves_with_config(values: &[T], hasher: H, config: Config) -> Result<Self, &'static str> {
+        use rayon::prelude::*;
+
+        if values.is_empty() {
+            return Err("Leaves cannot be empty");
+        }
+        config.check()?;
+
+        let leaf_nodes = values.par_iter()
+            .with_min_len(PARALLEL_MIN_LEN)
+            .map_init(|| hasher.clone(), |hasher, v| Self::leaf_node(v, config, hasher))
+            .collect::<Result<Vec<Node<T>>, &'static str>>()?;
+
+        Ok(Self::from_leaf_nodes_with(leaf_nodes, hasher, config, Self::par_build_parent_nodes))
+    }
+
+    fn par_build_parent_nodes(children: &[Node<T>], parents: &mut [Node<T>], padding: Option<&Hash>, config: Config, hasher: &mut H) {
+        use rayon::prelude::*;
+
+        if children.len() < PARALLEL_MIN_LEN * config.arity {
+            return Self::build_parent_nodes(children, parents, padding, config, hasher);
+        }
+
+        let hasher: &H = hasher;
+        parents.par_iter_mut()
+            .zip(children.par_chunks(config.arity))
+            .with_min_len(PARALLEL_MIN_LEN)
+            .for_each_init(|| hasher.clone(), |hasher, (parent, group)| *parent = Self::parent_node(group, padding, config, hasher));
+    }
+}
+
+// fewest nodes a rayon task hashes, below which splitting costs more than
+// it saves
+#[cfg(feature = "parallel")]
+const PARALLEL_MIN_LEN: usize = 256;
+
+/// Builds a `MerkleTree` from leaves pushed one at a time, such as rows
+/// read from a file or a database cursor. Each value is hashed as it is
+/// pushed; only its hash is kept when the config is hash-only. A value the
+/// hasher rejects fails `push`, or `build` when it came through `extend`.
+pub struct MerkleTreeBuilder<H, T>
+    where H: MerkleHasher,
+          T: Leaf + Clone,
+{
+    hasher: H,
+    config: Config,
+    leaves: Vec<Node<T>>,
+    // the first leaf the hasher rejected while extending, which `build`
+    // reports
+    error: Option<&'static str>,
+}
+
+impl<H, T> MerkleTreeBuilder<H, T>
+    where H: MerkleHasher,
+          T: Leaf + Clone,
+{
+    /// Checks `config` up front, so that a bad config fails before any
+    /// leaf is read.
+    pub fn new(hasher: H, config: Config) -> Result<Self, &'static str> {
+        config.check()?;
+
+        Ok(MerkleTreeBuilder {
+            hasher,
+            config,
+            leaves: vec![],
+            error: None,
+        })
+    }
+
+    pub fn push(&mut self, value: T) -> Result<(), &'static str> {
+        let leaf_node = MerkleTree::owned_leaf_node(value, self.config, &mut self.hasher)?;
+        self.leaves.push(leaf_node);
+        Ok(())
+    }
+
+    /// `push` for a borrowed value, which is only cloned when the config
+    /// keeps values.
+    pub fn push_ref(&mut self, value: &T) -> Result<(), &'static str> {
+        let leaf_node = MerkleTree::leaf_node(value, self.config, &mut self.hasher)?;
+        self.leaves.push(leaf_node);
+        Ok(())
+    }
+
+    /// Pushes a leaf hashed beforehand with `config.scheme.hash_leaf`. The
+    /// config must be hash-only.
+    pub fn push_hash(&mut self, hash: Hash) -> Result<(), &'static str> {
+        if !self.config.hash_only {
+            return Err("Tree is not hash-only");
+        }
+        let leaf_node = MerkleTree::<H, T>::hashed_leaf_node(hash, &self.hasher)?;
+        self.leaves.push(leaf_node);
+        Ok(())
+    }
+
+    /// Number of leaves pushed so far.
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn build(self) -> Result<MerkleTree<H, T>, &'static str> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.leaves.is_empty() {
+            return Err("Leaves cannot be empty");
+        }
+        Ok(MerkleTree::from_leaf_nodes(self.leaves, self.hasher, self.config))
+    }
+}
+
+impl<H, T> Extend<T> for MerkleTreeBuilder<H, T>
+    where H: MerkleHasher,
+          T: Leaf + Clone,
+{
+    fn extend<I>(&mut self, values: I)
+        where I: IntoIterator<Item = T>,
+    {
+        let values = values.into_iter();
+        self.leaves.reserve(values.size_hint().0);
+        for v in values {
+            if let Err(error) = self.push(v) {
+                self.error.get_or_insert(error);
+            }
         }
     }
 }
 
-#[cfg(test)]
+// The levels of a tree, leaves first, laid out one after the other in a
+// single buffer. Each level has room for the nodes of a tree over
+// `capacity` leaves, so that the tree only moves to a bigger buffer, like a
+// `Vec` does, when its leaves outgrow the capacity. Unused room holds
+// vacant nodes.
+struct Levels<T>
+    where T: Leaf + Clone,
+{
+    nodes: Vec<Node<T>>,
+    // start of each level's room in `nodes`
+    offsets: Vec<usize>,
+    // nodes in use at each level, up to the root
+    widths: Vec<usize>,
+    arity: usize,
+}
+
+impl<T> Default for Levels<T>
+    where T: Leaf + Clone,
+{
+    fn default() -> Self {
+        Levels {
+            nodes: vec![],
+            offsets: vec![],
+            widths: vec![],
+            arity: 2,
+        }
+    }
+}
+
+impl<T> Levels<T>
+    where T: Leaf + Clone,
+{
+    // the levels of a tree over `leaf_nodes`, with room for no more leaves;
+    // the nodes above the leaves are vacant until hashed
+    fn new(mut leaf_nodes: Vec<Node<T>>, arity: usize) -> Self {
+        let offsets = Self::layout(leaf_nodes.len(), arity);
+        let mut widths = vec![leaf_nodes.len()];
+        while widths[widths.len() - 1] > 1 {
+            widths.push(widths[widths.len() - 1].div_ceil(arity));
+        }
+
+        let total = offsets[offsets.len() - 1];
+        leaf_nodes.reserve_exact(total - leaf_nodes.len());
+        leaf_nodes.resize_with(total, Node::vacant);
+
+        Levels {
+            nodes: leaf_nodes,
+            offsets,
+            widths,
+            arity,
+        }
+    }
+
+    // start of each level of a tree over `capacity` leaves, followed by the
+    // total length
+    fn layout(capacity: usize, arity: usize) -> Vec<usize> {
+        let mut offsets = vec![0];
+        let mut width = capacity;
+        loop {
+            offsets.push(offsets[offsets.len() - 1] + width);
+            if width <= 1 {
+                return offsets;
+            }
+            width = width.div_ceil(arity);
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.offsets.get(1).copied().unwrap_or(0)
+    }
+
+    // number of levels in use
+    fn len(&self) -> usize {
+        self.widths.len()
+    }
+
+    fn first(&self) -> Option<&[Node<T>]> {
+        (!self.widths.is_empty()).then(|| &self[0])
+    }
+
+    fn last(&self) -> Option<&[Node<T>]> {
+        (!self.widths.is_empty()).then(|| &self[self.len() - 1])
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &[Node<T>]> {
+        (0..self.len()).map(move |level| &self[level])
+    }
+
+    // `level` and the level above it
+    fn split_at_mut(&mut self, level: usize) -> (&[Node<T>], &mut [Node<T>]) {
+        let (below, above) = self.nodes.split_at_mut(self.offsets[level + 1]);
+        let children = &below[self.offsets[level]..self.offsets[level] + self.widths[level]];
+        (children, &mut above[..self.widths[level + 1]])
+    }
+
+    // sets the width of `level`, which may be the next level up; nodes it
+    // gains are vacant until hashed, nodes it loses are dropped
+    fn resize(&mut self, level: usize, width: usize) {
+        if level == self.len() {
+            self.widths.push(0);
+        }
+        let start = self.offsets[level];
+        let old = self.widths[level];
+        for node in &mut self.nodes[start + width.min(old)..start + old] {
+            *node = Node::vacant();
+        }
+        self.widths[level] = width;
+    }
+
+    // drops the levels above the first `len`
+    fn truncate(&mut self, len: usize) {
+        for level in len..self.len() {
+            self.resize(level, 0);
+        }
+        self.widths.truncate(len);
+    }
+
+    fn insert_leaf(&mut self, index: usize, node: Node<T>) {
+        let width = self.widths[0];
+        if width == self.capacity() {
+            self.grow(width + 1);
+        }
+        self.nodes[width] = node;
+        self.nodes[index..=width].rotate_right(1);
+        self.widths[0] += 1;
+    }
+
+    fn remove_leaf(&mut self, index: usize) -> Node<T> {
+        let width = self.widths[0];
+        self.nodes[index..width].rotate_left(1);
+        self.widths[0] -= 1;
+        std::mem::replace(&mut self.nodes[width - 1], Node::vacant())
+    }
+
+    fn swap_remove_leaf(&mut self, index: usize) -> Node<T> {
+        let width = self.widths[0];
+        self.nodes.swap(index, width - 1);
+        self.widths[0] -= 1;
+        std::mem::replace(&mut self.nodes[width - 1], Node::vacant())
+    }
+
+    fn truncate_leaves(&mut self, len: usize) {
+        self.resize(0, len);
+    }
+
+    // moves to a buffer with room for at least `capacity` leaves, doubling
+    // the room so that appending leaves moves each node O(1) times
+    fn grow(&mut self, capacity: usize) {
+        let capacity = capacity.max(2 * self.capacity());
+        let offsets = Self::layout(capacity, self.arity);
+
+        let mut old = std::mem::take(&mut self.nodes);
+        let mut nodes = Vec::with_capacity(offsets[offsets.len() - 1]);
+        for level in 0..offsets.len() - 1 {
+            if level < self.len() {
+                let start = self.offsets[level];
+                nodes.extend(old[start..start + self.widths[level]].iter_mut().map(|node| std::mem::replace(node, Node::vacant())));
+            }
+            nodes.resize_with(offsets[level + 1], Node::vacant);
+        }
+
+        self.nodes = nodes;
+        self.offsets = offsets;
+    }
+}
+
+impl<T> Index<usize> for Levels<T>
+    where T: Leaf + Clone,
+{
+    type Output = [Node<T>];
+
+    fn index(&self, level: usize) -> &[Node<T>] {
+        let start = self.offsets[level];
+        &self.nodes[start..start + self.widths[level]]
+    }
+}
+
+impl<T> IndexMut<usize> for Levels<T>
+    where T: Leaf + Clone,
+{
+    fn index_mut(&mut self, level: usize) -> &mut [Node<T>] {
+        let start = self.offsets[level];
+        &mut self.nodes[start..start + self.widths[level]]
+    }
+}
+
+#[cfg(all(test, feature = "rust-crypto"))]
 mod tests {
+    use crypto::digest::Digest;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
     use crypto::sha2::Sha256;
     use super::*;
 
+    // counts the digests computed through it
+    struct CountingHasher {
+        inner: Sha256,
+        count: Rc<Cell<usize>>,
+    }
+
+    impl Digest for CountingHasher {
+        fn input(&mut self, input: &[u8]) {
+            self.inner.input(input)
+        }
+
+        fn result(&mut self, out: &mut [u8]) {
+            self.count.set(self.count.get() + 1);
+            self.inner.result(out)
+        }
+
+        fn reset(&mut self) {
+            self.inner.reset()
+        }
+
+        fn output_bits(&self) -> usize {
+            self.inner.output_bits()
+        }
+
+        fn block_size(&self) -> usize {
+            self.inner.block_size()
+        }
+    }
+
     #[test]
     fn test_as_leaf() {
         let mut hasher = Sha256::new();
-        let leaf_node: Node<String> = MerkleTree::as_leaf(&String::from("tea"), &mut hasher);
+        let leaf_node: Node<String> = MerkleTree::as_leaf(&String::from("tea"), HashScheme::Unprefixed, &mut hasher).unwrap();
 
         assert_eq!(leaf_node.value, Some(String::from("tea")));
-        assert_eq!(leaf_node.hash, "a9f74d1ec36ebdeb2da3f6e5868090cd2a2d20b3dcca7b62f60304b1d3d9ef42");
+        assert_eq!(leaf_node.hash.to_hex(), "a9f74d1ec36ebdeb2da3f6e5868090cd2a2d20b3dcca7b62f60304b1d3d9ef42");
     }
 
     #[test]
     fn test_as_internal() {
         let mut hasher = Sha256::new();
-        let leaf_node_left: Node<String> = MerkleTree::as_leaf(&String::from("tea"), &mut hasher);
-        let leaf_node_right: Node<String> = MerkleTree::as_leaf(&String::from("coffee"), &mut hasher);
-        let parent_node: Node<String> = MerkleTree::as_internal(&leaf_node_left, &leaf_node_right, &mut hasher);
+        let leaf_node_left: Node<String> = MerkleTree::as_leaf(&String::from("tea"), HashScheme::Unprefixed, &mut hasher).unwrap();
+        let leaf_node_right: Node<String> = MerkleTree::as_leaf(&String::from("coffee"), HashScheme::Unprefixed, &mut hasher).unwrap();
+        let parent_node: Node<String> = MerkleTree::as_internal(&leaf_node_left, &leaf_node_right, HashScheme::Unprefixed, &mut hasher);
 
         assert_eq!(parent_node.value, None);
-        assert_eq!(parent_node.hash, "d050213312c90773722bdb448110143b042d5f13de000e93b68a8769453ba38d");
+        assert_eq!(parent_node.hash.to_hex(), "da358a5fff8c2144a68442a0aff90bb0bfd812b8842ea34c067baac3d734fdfc");
     }
 
     #[test]
     fn test_from_leaves_2n() {
-        let mut leaf_values: Vec<String> = vec![
+        let leaf_values: Vec<String> = vec![
             String::from("tea"),
             String::from("coffee"),
             String::from("lemonade"),
             String::from("wine")
         ];
-        if let Some(mt) = MerkleTree::from_leaves(&mut leaf_values, Sha256::new()).ok() {
-            assert_eq!(mt.nodes.len(), 7 as usize);
-            assert_eq!(mt.nodes[0].value, Some(String::from("tea")));
-            assert_eq!(mt.nodes[1].value, Some(String::from("coffee")));
-            assert_eq!(mt.nodes[2].value, Some(String::from("lemonade")));
-            assert_eq!(mt.nodes[3].value, Some(String::from("wine")));
+        let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        assert_eq!(mt.levels.len(), 3);
+        assert_eq!(mt.levels[0].len(), 4);
+        assert_eq!(mt.levels[0][0].value, Some(String::from("tea")));
+        assert_eq!(mt.levels[0][1].value, Some(String::from("coffee")));
+        assert_eq!(mt.levels[0][2].value, Some(String::from("lemonade")));
+        assert_eq!(mt.levels[0][3].value, Some(String::from("wine")));
 
-            assert_eq!(mt.nodes[4].value, None);
-            assert_eq!(mt.nodes[4].hash, "d050213312c90773722bdb448110143b042d5f13de000e93b68a8769453ba38d");
+        assert_eq!(mt.levels[1][0].value, None);
+        assert_eq!(mt.levels[1][0].hash.to_hex(), "da358a5fff8c2144a68442a0aff90bb0bfd812b8842ea34c067baac3d734fdfc");
 
-            assert_eq!(mt.nodes[5].value, None);
-            assert_eq!(mt.nodes[5].hash, "f6c1118a17527ef7c6addbe574fa8c2256f98764cab46568c6bc7ab70e1ee808");
+        assert_eq!(mt.levels[1][1].value, None);
+        assert_eq!(mt.levels[1][1].hash.to_hex(), "deeeedc796079d9cf0762e8f0f261eced1b11ba1d023abef52066abb61d14a08");
 
-            assert_eq!(mt.nodes[6].value, None);
-            assert_eq!(mt.nodes[6].hash, "0e3bc6149e1f99b5192e73c92328a7e4bb95df94ad9b96253698418a2e746766");
-        } else {
-            assert!(false);
-        }
+        assert_eq!(mt.levels[2][0].value, None);
+        assert_eq!(mt.levels[2][0].hash.to_hex(), "f327fcb35cf8b8a2bef2ef7a58695c914ab0f1dce982c57b9176886a29b86fc2");
     }
 
     #[test]
     fn test_from_leaves_not_2n() {
-        let mut leaf_values: Vec<String> = vec![
+        let leaf_values: Vec<String> = vec![
             String::from("tea"),
             String::from("coffee"),
             String::from("lemonade"),
@@ -226,52 +1255,52 @@ mod tests {
             String::from("pepsi"),
             String::from("cola")
         ];
-        if let Some(mt) = MerkleTree::from_leaves(&mut leaf_values, Sha256::new()).ok() {
-            assert_eq!(mt.nodes.len(), 15 as usize);
-            assert_eq!(mt.nodes[0].value, Some(String::from("tea")));
-            assert_eq!(mt.nodes[1].value, Some(String::from("coffee")));
-            assert_eq!(mt.nodes[2].value, Some(String::from("lemonade")));
-            assert_eq!(mt.nodes[3].value, Some(String::from("wine")));
-            assert_eq!(mt.nodes[4].value, Some(String::from("pepsi")));
-            assert_eq!(mt.nodes[5].value, Some(String::from("cola")));
-            assert_eq!(mt.nodes[6].value, Some(String::from("cola")));
-            assert_eq!(mt.nodes[7].value, Some(String::from("cola")));
+        let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        assert_eq!(mt.levels.len(), 4);
+        assert_eq!(mt.levels[0].len(), 6);
+        assert_eq!(mt.levels[0][0].value, Some(String::from("tea")));
+        assert_eq!(mt.levels[0][1].value, Some(String::from("coffee")));
+        assert_eq!(mt.levels[0][2].value, Some(String::from("lemonade")));
+        assert_eq!(mt.levels[0][3].value, Some(String::from("wine")));
+        assert_eq!(mt.levels[0][4].value, Some(String::from("pepsi")));
+        assert_eq!(mt.levels[0][5].value, Some(String::from("cola")));
 
-            assert_eq!(mt.nodes[8].value, None);
-            assert_eq!(mt.nodes[8].hash, "d050213312c90773722bdb448110143b042d5f13de000e93b68a8769453ba38d");
+        // padding is not stored, the caller's values are left untouched
+        assert_eq!(leaf_values.len(), 6);
+        assert_eq!(mt.padding[0], mt.levels[0][5].hash);
 
-            assert_eq!(mt.nodes[9].value, None);
-            assert_eq!(mt.nodes[9].hash, "f6c1118a17527ef7c6addbe574fa8c2256f98764cab46568c6bc7ab70e1ee808");
+        assert_eq!(mt.levels[1][0].value, None);
+        assert_eq!(mt.levels[1][0].hash.to_hex(), "da358a5fff8c2144a68442a0aff90bb0bfd812b8842ea34c067baac3d734fdfc");
 
-            assert_eq!(mt.nodes[10].value, None);
-            assert_eq!(mt.nodes[10].hash, "0f932c1de87f02001cca7bb3e7e9982db2cf0022a601461ed51da468c7caa423");
+        assert_eq!(mt.levels[1][1].value, None);
+        assert_eq!(mt.levels[1][1].hash.to_hex(), "deeeedc796079d9cf0762e8f0f261eced1b11ba1d023abef52066abb61d14a08");
 
-            assert_eq!(mt.nodes[11].value, None);
-            assert_eq!(mt.nodes[11].hash, "97c9f489762d8909272edbd6aeec2a6e75916604dc8e087d82dcae43b082a8dc");
+        assert_eq!(mt.levels[1][2].value, None);
+        assert_eq!(mt.levels[1][2].hash.to_hex(), "613a89698529fbc712ea373b2b6d29c5c03ad4d28a7acf4228de68d895a66011");
 
-            assert_eq!(mt.nodes[12].value, None);
-            assert_eq!(mt.nodes[12].hash, "0e3bc6149e1f99b5192e73c92328a7e4bb95df94ad9b96253698418a2e746766");
+        assert_eq!(mt.levels[1].len(), 3);
+        assert_eq!(mt.padding[1].to_hex(), "4ef7907f43db171e95deac61d3861894227d135ac0d2d27c7683df72e3355022");
 
-            assert_eq!(mt.nodes[13].value, None);
-            assert_eq!(mt.nodes[13].hash, "7c5bf950be2daf8381ab6fb02ad6d66727fc02b2a793d01e60fab5a795736179");
+        assert_eq!(mt.levels[2][0].value, None);
+        assert_eq!(mt.levels[2][0].hash.to_hex(), "f327fcb35cf8b8a2bef2ef7a58695c914ab0f1dce982c57b9176886a29b86fc2");
 
-            assert_eq!(mt.nodes[14].value, None);
-            assert_eq!(mt.nodes[14].hash, "93993d7a938d03233784c7b480e32665b483542bd2d22e09bdd6dd590874d5c6");
+        assert_eq!(mt.levels[2][1].value, None);
+        assert_eq!(mt.levels[2][1].hash.to_hex(), "ed80cee7334f87f24927b9854fe24db8eb9dcd6cc7857ffb4ea2348f81a21c4c");
 
-            let root = mt.root().ok().unwrap();
-            assert_eq!(root.value, None);
-            assert_eq!(root.hash, "93993d7a938d03233784c7b480e32665b483542bd2d22e09bdd6dd590874d5c6");
+        assert_eq!(mt.levels[3][0].value, None);
+        assert_eq!(mt.levels[3][0].hash.to_hex(), "a319d5ff88ec23b8694c166b086c5061f90794791b541f2b963138ebaaaa0ac8");
 
-            let count_leaves = mt.count_leaves().ok().unwrap();
-            assert_eq!(count_leaves, 8usize);
-        } else {
-            assert!(false);
-        }
+        let root = mt.root().ok().unwrap();
+        assert_eq!(root.value, None);
+        assert_eq!(root.hash.to_hex(), "a319d5ff88ec23b8694c166b086c5061f90794791b541f2b963138ebaaaa0ac8");
+
+        let count_leaves = mt.count_leaves().ok().unwrap();
+        assert_eq!(count_leaves, 8usize);
     }
 
     #[test]
     fn test_add_leaves() {
-        let mut leaf_values: Vec<String> = vec![
+        let leaf_values: Vec<String> = vec![
             String::from("tea"),
             String::from("coffee"),
             String::from("lemonade"),
@@ -279,32 +1308,486 @@ mod tests {
             String::from("pepsi"),
             String::from("cola")
         ];
-        if let Some(mut mt) = MerkleTree::from_leaves(&mut leaf_values, Sha256::new()).ok() {
-            let mut root = mt.root().ok().unwrap();
-            assert_eq!(root.value, None);
-            assert_eq!(root.hash, "93993d7a938d03233784c7b480e32665b483542bd2d22e09bdd6dd590874d5c6");
-
-            let mut new_values: Vec<String> = vec![
-                String::from("beer"),
-                String::from("whisky")
-            ];
-            mt.add_leaves(&mut new_values);
-
-            // these parents don't change
-            assert_eq!(mt.nodes[8].hash, "d050213312c90773722bdb448110143b042d5f13de000e93b68a8769453ba38d");
-            assert_eq!(mt.nodes[9].hash, "f6c1118a17527ef7c6addbe574fa8c2256f98764cab46568c6bc7ab70e1ee808");
-            assert_eq!(mt.nodes[10].hash, "0f932c1de87f02001cca7bb3e7e9982db2cf0022a601461ed51da468c7caa423");
-            assert_eq!(mt.nodes[12].hash, "0e3bc6149e1f99b5192e73c92328a7e4bb95df94ad9b96253698418a2e746766");
-
-            // parents are updated
-            assert_eq!(mt.nodes[11].hash, "5b85aa79636ae07cfde54820867e1208ce80d8830c96fc0468877ea7048bb36a");
-            assert_eq!(mt.nodes[13].hash, "0e1364864c336487e488b4e2724412bc28cbf93a1233d34c849aa9a61032157b");
-            assert_eq!(mt.nodes[14].hash, "99f2eeb65950f598256a9d04084ec41ab9efcdbc573610fdad74162cebac5ac1");
-
-            root = mt.root().ok().unwrap();
-            assert_eq!(root.hash, "99f2eeb65950f598256a9d04084ec41ab9efcdbc573610fdad74162cebac5ac1");
-        } else {
-            assert!(false);
+        let mut mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        let mut root = mt.root().ok().unwrap();
+        assert_eq!(root.value, None);
+        assert_eq!(root.hash.to_hex(), "a319d5ff88ec23b8694c166b086c5061f90794791b541f2b963138ebaaaa0ac8");
+
+        let new_values: Vec<String> = vec![
+            String::from("beer"),
+            String::from("whisky")
+        ];
+        mt.add_leaves(&new_values).unwrap();
+
+        // these parents don't change
+        assert_eq!(mt.levels[1][0].hash.to_hex(), "da358a5fff8c2144a68442a0aff90bb0bfd812b8842ea34c067baac3d734fdfc");
+        assert_eq!(mt.levels[1][1].hash.to_hex(), "deeeedc796079d9cf0762e8f0f261eced1b11ba1d023abef52066abb61d14a08");
+        assert_eq!(mt.levels[1][2].hash.to_hex(), "613a89698529fbc712ea373b2b6d29c5c03ad4d28a7acf4228de68d895a66011");
+        assert_eq!(mt.levels[2][0].hash.to_hex(), "f327fcb35cf8b8a2bef2ef7a58695c914ab0f1dce982c57b9176886a29b86fc2");
+
+        // parents are updated
+        assert_eq!(mt.levels[1][3].hash.to_hex(), "8328cfeee8314097644f7f4e3b3fe79a35b794c2f2b6ce5d6a55213f2af93cbc");
+        assert_eq!(mt.levels[2][1].hash.to_hex(), "f5d1f6f70755abf7f18d4d6343f79d9ee029f57f992b2bbccc2ae20512b31c73");
+        assert_eq!(mt.levels[3][0].hash.to_hex(), "66c67e65ac81acdb903f1a84940e1d45a5387e86b8da7d717cd96a9912ac3599");
+
+        root = mt.root().ok().unwrap();
+        assert_eq!(root.hash.to_hex(), "66c67e65ac81acdb903f1a84940e1d45a5387e86b8da7d717cd96a9912ac3599");
+    }
+
+    #[test]
+    fn test_from_leaves_domain_separated() {
+        let leaf_values: Vec<String> = vec![
+            String::from("tea"),
+            String::from("coffee"),
+            String::from("lemonade"),
+            String::from("wine"),
+            String::from("pepsi"),
+            String::from("cola")
+        ];
+        let config = Config { scheme: HashScheme::DomainSeparated, ..Config::default() };
+        let mut mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+        assert_eq!(mt.levels[0][0].hash.to_hex(), "fbae8de98ede70870b432b82f238a72ca1d1b008cd0694f89f59ce4fc2897fd2");
+        assert_eq!(mt.levels[1][0].hash.to_hex(), "48cde61125df0fd8ff8606191ddef660e51a36a451c3dfd78e6a5c23c1fe33ad");
+        assert_eq!(mt.padding[1].to_hex(), "19af2a1de4cc693711ef480bca22c35342130ae4131a5fc3229fcf20d5e71519");
+        assert_eq!(mt.root().unwrap().hash.to_hex(), "d16a378fca70a1d7ecab4f050f10deb29b5832dd0d7ce7b5e6147179c227d071");
+
+        let new_values: Vec<String> = vec![
+            String::from("beer"),
+            String::from("whisky")
+        ];
+        mt.add_leaves(&new_values).unwrap();
+        assert_eq!(mt.levels[1][3].hash.to_hex(), "7e52eef75fa57446b15889deaf3abafd61e79fe25de908dabf70c43cbfac4578");
+        assert_eq!(mt.root().unwrap().hash.to_hex(), "9f5f7d99382e3083abf5a6188f08b59b46bf634995b3c84af3f5746eadb5478b");
+    }
+
+    #[test]
+    fn test_padding_strategies() {
+        let leaf_values: Vec<String> = vec![
+            String::from("tea"),
+            String::from("coffee"),
+            String::from("lemonade"),
+            String::from("wine"),
+            String::from("pepsi"),
+            String::from("cola")
+        ];
+        let build = |padding| {
+            let config = Config { padding, ..Config::default() };
+            MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap()
+        };
+
+        let mt = build(PaddingStrategy::Zero);
+        assert_eq!(mt.padding[0], Hash::zero(32));
+        assert_eq!(mt.count_leaves().unwrap(), 8);
+        assert_eq!(mt.levels[2][1].hash.to_hex(), "2fc97afe4f476d2c47c76fd6d8646b4de93a6e9f7e4f17947e85076860951204");
+        assert_eq!(mt.root_hash().unwrap().to_hex(), "a5389837de39fe45ffa67ce570cb24d3195832e05be31dbdfa92073794ae51c3");
+
+        // the third parent is paired with itself on its way up
+        let mt = build(PaddingStrategy::DuplicateOdd);
+        assert!(mt.padding.is_empty());
+        assert_eq!(mt.count_leaves().unwrap(), 6);
+        assert_eq!(mt.root_hash().unwrap().to_hex(), "4b0408632b1810f1afd67c71a60c82a7426510d432c44967cd18f127bc63f531");
+
+        // the third parent is promoted as it is
+        let mt = build(PaddingStrategy::Unbalanced);
+        assert!(mt.padding.is_empty());
+        assert_eq!(mt.count_leaves().unwrap(), 6);
+        assert_eq!(mt.levels[2][1].hash, mt.levels[1][2].hash);
+        assert_eq!(mt.root_hash().unwrap().to_hex(), "ab747701fa42c385dc8cfec8073b6b662da02e1ef401fdf831372e1531444933");
+    }
+
+    #[test]
+    fn test_padding_duplicate_root() {
+        let three: Vec<String> = vec![
+            String::from("tea"),
+            String::from("coffee"),
+            String::from("lemonade")
+        ];
+        let mut four = three.clone();
+        four.push(String::from("lemonade"));
+
+        let roots = |padding| {
+            let config = Config { padding, ..Config::default() };
+            let a = MerkleTree::from_leaves_with_config(&three, Sha256::new(), config).unwrap();
+            let b = MerkleTree::from_leaves_with_config(&four, Sha256::new(), config).unwrap();
+            (a.root_hash().unwrap().clone(), b.root_hash().unwrap().clone())
+        };
+
+        let (a, b) = roots(PaddingStrategy::DuplicateLast);
+        assert_eq!(a, b);
+        let (a, b) = roots(PaddingStrategy::DuplicateOdd);
+        assert_eq!(a, b);
+        let (a, b) = roots(PaddingStrategy::Zero);
+        assert_ne!(a, b);
+        assert_eq!(a.to_hex(), "4dc96f319f87b01c0de4e089ee73bb6a09dcb8761eebf53783c44ea3f55a0d9e");
+        let (a, b) = roots(PaddingStrategy::Unbalanced);
+        assert_ne!(a, b);
+        assert_eq!(a.to_hex(), "1027089527e99660d87184cf9be3d8149f7dad188ab7180ddbaae660c14177ca");
+    }
+
+    #[test]
+    fn test_add_leaf_matches_rebuild() {
+        let strategies = [
+            PaddingStrategy::DuplicateLast,
+            PaddingStrategy::Zero,
+            PaddingStrategy::DuplicateOdd,
+            PaddingStrategy::Unbalanced,
+        ];
+        let leaf_values: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        for &padding in strategies.iter() {
+            let config = Config { padding, ..Config::default() };
+            let mut mt = MerkleTree::from_leaves_with_config(&leaf_values[..1], Sha256::new(), config).unwrap();
+            for n in 2..=leaf_values.len() {
+                mt.add_leaf(&leaf_values[n - 1]).unwrap();
+
+                let expected = MerkleTree::from_leaves_with_config(&leaf_values[..n], Sha256::new(), config).unwrap();
+                assert_eq!(mt.levels.len(), expected.levels.len());
+                for (level, expected_level) in mt.levels.iter().zip(expected.levels.iter()) {
+                    let hashes: Vec<&Hash> = level.iter().map(Node::hash).collect();
+                    let expected_hashes: Vec<&Hash> = expected_level.iter().map(Node::hash).collect();
+                    assert_eq!(hashes, expected_hashes);
+                }
+                assert_eq!(mt.padding, expected.padding);
+            }
         }
     }
+
+    #[test]
+    fn test_add_leaf_hashes_path_only() {
+        let leaf_values: Vec<String> = (0..1024).map(|i| i.to_string()).collect();
+        let count = Rc::new(Cell::new(0));
+        let hasher = CountingHasher { inner: Sha256::new(), count: count.clone() };
+        let config = Config { padding: PaddingStrategy::DuplicateOdd, ..Config::default() };
+        let mut mt = MerkleTree::from_leaves_with_config(&leaf_values, hasher, config).unwrap();
+        assert_eq!(mt.levels.len(), 11);
+
+        // the leaf, then one parent per level up to a new root level
+        count.set(0);
+        mt.add_leaf(&String::from("1024")).unwrap();
+        assert_eq!(count.get(), 1 + 11);
+        assert_eq!(mt.levels.len(), 12);
+
+        // the duplicated padding is hashed again along with the path
+        let hasher = CountingHasher { inner: Sha256::new(), count: count.clone() };
+        let mut mt = MerkleTree::from_leaves(&leaf_values, hasher).unwrap();
+        count.set(0);
+        mt.add_leaf(&String::from("1024")).unwrap();
+        assert_eq!(count.get(), 1 + 11 + 11);
+    }
+
+    #[test]
+    fn test_update_leaves_matches_rebuild() {
+        let strategies = [
+            PaddingStrategy::DuplicateLast,
+            PaddingStrategy::Zero,
+            PaddingStrategy::DuplicateOdd,
+            PaddingStrategy::Unbalanced,
+        ];
+        for &padding in strategies.iter() {
+            let config = Config { padding, ..Config::default() };
+            for n in 1..=9 {
+                let mut leaf_values: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+                let mut mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+
+                for i in 0..n {
+                    leaf_values[i] = format!("updated {}", i);
+                    let root = mt.update_leaf(i, &leaf_values[i]).unwrap().clone();
+
+                    let expected = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+                    assert_eq!(&root, expected.root_hash().unwrap());
+                    assert_eq!(mt.padding, expected.padding);
+                }
+
+                let updates: Vec<(usize, String)> = (0..n).step_by(3).map(|i| (i, format!("batch {}", i))).collect();
+                for (i, v) in &updates {
+                    leaf_values[*i] = v.clone();
+                }
+                let root = mt.update_leaves(&updates).unwrap().clone();
+                let expected = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+                assert_eq!(&root, expected.root_hash().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_leaves_shares_ancestors() {
+        let leaf_values: Vec<String> = (0..8).map(|i| i.to_string()).collect();
+        let count = Rc::new(Cell::new(0));
+        let hasher = CountingHasher { inner: Sha256::new(), count: count.clone() };
+        let mut mt = MerkleTree::from_leaves(&leaf_values, hasher).unwrap();
+
+        // two leaves, then their three ancestors once
+        count.set(0);
+        let updates = vec![(0, String::from("a")), (1, String::from("b"))];
+        mt.update_leaves(&updates).unwrap();
+        assert_eq!(count.get(), 2 + 3);
+
+        // the last value for a repeated index wins
+        let updates = vec![(2, String::from("c")), (2, String::from("d"))];
+        mt.update_leaves(&updates).unwrap();
+        assert_eq!(mt.levels[0][2].value, Some(String::from("d")));
+
+        assert!(mt.update_leaf(8, &String::from("e")).is_err());
+    }
+
+    #[test]
+    fn test_remove_leaf_matches_rebuild() {
+        let strategies = [
+            PaddingStrategy::DuplicateLast,
+            PaddingStrategy::Zero,
+            PaddingStrategy::DuplicateOdd,
+            PaddingStrategy::Unbalanced,
+        ];
+        for &padding in strategies.iter() {
+            let config = Config { padding, ..Config::default() };
+            for n in 2..=9 {
+                for index in 0..n {
+                    for &removal in [Removal::Swap, Removal::Shift].iter() {
+                        let mut leaf_values: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+                        let mut mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+
+                        let removed = mt.remove_leaf(index, removal).unwrap();
+                        let value = match removal {
+                            Removal::Swap => leaf_values.swap_remove(index),
+                            Removal::Shift => leaf_values.remove(index),
+                        };
+                        assert_eq!(removed.value, Some(value));
+
+                        let expected = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+                        assert_eq!(mt.levels.len(), expected.levels.len());
+                        assert_eq!(mt.root_hash(), expected.root_hash());
+                        assert_eq!(mt.count_leaves(), expected.count_leaves());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_truncate() {
+        let leaf_values: Vec<String> = (0..9).map(|i| i.to_string()).collect();
+        for len in 1..=9 {
+            let mut mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+            mt.truncate(len).unwrap();
+
+            let expected = MerkleTree::from_leaves(&leaf_values[..len], Sha256::new()).unwrap();
+            assert_eq!(mt.len(), len);
+            assert_eq!(mt.levels.len(), expected.levels.len());
+            assert_eq!(mt.padding, expected.padding);
+            assert_eq!(mt.root_hash(), expected.root_hash());
+        }
+
+        let mut mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        assert_eq!(mt.levels.len(), 5);
+        mt.truncate(8).unwrap();
+        assert_eq!(mt.levels.len(), 4);
+        assert_eq!(mt.count_leaves().unwrap(), 8);
+        mt.truncate(3).unwrap();
+        assert_eq!(mt.levels.len(), 3);
+        assert_eq!(mt.count_leaves().unwrap(), 4);
+
+        assert!(mt.truncate(0).is_err());
+        assert!(mt.truncate(5).is_ok());
+        assert_eq!(mt.len(), 3);
+    }
+
+    #[test]
+    fn test_sorted_leaves() {
+        let leaf_values: Vec<String> = ["wine", "tea", "cola", "pepsi"].iter().map(|v| v.to_string()).collect();
+        let config = Config { order: LeafOrder::ByValue, ..Config::default() };
+        let mut mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+
+        mt.add_leaf(&String::from("coffee")).unwrap();
+        mt.add_leaf(&String::from("zinfandel")).unwrap();
+        let sorted: Vec<&String> = mt.leaves().map(|leaf| leaf.value().unwrap()).collect();
+        assert_eq!(sorted, ["coffee", "cola", "pepsi", "tea", "wine", "zinfandel"]);
+
+        let expected = MerkleTree::from_leaves(&sorted.into_iter().cloned().collect::<Vec<_>>(), Sha256::new()).unwrap();
+        assert_eq!(mt.root_hash(), expected.root_hash());
+
+        assert!(mt.update_leaf(0, &String::from("beer")).is_err());
+        assert!(mt.remove_leaf(0, Removal::Swap).is_err());
+        mt.remove_leaf(0, Removal::Shift).unwrap();
+        assert_eq!(mt.leaf(0).unwrap().value(), Some(&String::from("cola")));
+
+        let config = Config { order: LeafOrder::ByHash, ..Config::default() };
+        let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+        let hashes: Vec<&Hash> = mt.leaves().map(|leaf| leaf.hash()).collect();
+        assert!(hashes.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_hash_only() {
+        let leaf_values: Vec<String> = (0..9).map(|i| i.to_string()).collect();
+        for &arity in [2, 4].iter() {
+            let config = Config { arity, ..Config::default() };
+            let hash_only = Config { hash_only: true, ..config };
+            let mut mt = MerkleTree::from_leaves_with_config(&leaf_values[..5], Sha256::new(), hash_only).unwrap();
+            assert!(mt.leaves().all(|leaf| leaf.is_leaf() && leaf.value().is_none()));
+
+            mt.add_leaves(&leaf_values[5..8]).unwrap();
+            let hash = config.scheme.hash_leaf(&leaf_values[8], &mut Sha256::new());
+            mt.add_leaf_hash(hash).unwrap();
+            let expected = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+            assert_eq!(mt.root_hash(), expected.root_hash());
+            assert_eq!(mt.levels.len(), expected.levels.len());
+
+            let hashes: Vec<Hash> = expected.leaves().map(|leaf| leaf.hash().clone()).collect();
+            let from_hashes: MerkleTree<Sha256, String> = MerkleTree::from_hashes(&hashes, Sha256::new(), config).unwrap();
+            assert!(from_hashes.config().hash_only);
+            assert_eq!(from_hashes.root_hash(), expected.root_hash());
+        }
+
+        let mut mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), Config { hash_only: true, ..Config::default() }).unwrap();
+        assert_eq!(mt.value(3, &leaf_values), Ok(String::from("3")));
+        assert!(mt.value(3, &leaf_values[..3]).is_err());
+        assert!(mt.value(9, &leaf_values).is_err());
+
+        let mut updated = leaf_values.clone();
+        updated[3] = String::from("three");
+        let root = mt.update_leaf(3, &updated[3]).unwrap().clone();
+        assert_eq!(mt.leaf(3).unwrap().value(), None);
+        assert_eq!(Some(&root), MerkleTree::from_leaves(&updated, Sha256::new()).unwrap().root_hash().ok());
+        // the store must agree with the tree
+        assert!(mt.value(3, &leaf_values).is_err());
+        assert_eq!(mt.value(3, &updated), Ok(String::from("three")));
+
+        let hash = HashScheme::default().hash_leaf(&leaf_values[3], &mut Sha256::new());
+        mt.update_leaf_hash(3, hash).unwrap();
+        assert_eq!(mt.value(3, &leaf_values), Ok(String::from("3")));
+
+        let mut retained = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        assert_eq!(retained.value(3, &[][..]), Ok(String::from("3")));
+        assert!(retained.add_leaf_hash(Hash::new(&[0; 32])).is_err());
+        assert!(retained.update_leaf_hash(0, Hash::new(&[0; 32])).is_err());
+
+        let by_value = Config { hash_only: true, order: LeafOrder::ByValue, ..Config::default() };
+        assert!(MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), by_value).is_err());
+    }
+
+    // a leaf counting how many times it is cloned
+    struct Counted(u32);
+
+    thread_local! {
+        static CLONES: Cell<usize> = const { Cell::new(0) };
+    }
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            CLONES.with(|clones| clones.set(clones.get() + 1));
+            Counted(self.0)
+        }
+    }
+
+    impl Leaf for Counted {
+        fn leaf_bytes(&self) -> Cow<'_, [u8]> {
+            Cow::Owned(self.0.to_be_bytes().to_vec())
+        }
+    }
+
+    #[test]
+    fn test_hash_only_borrowed_leaves() {
+        let leaf_values: Vec<Counted> = (0..9).map(Counted).collect();
+        let hash_only = Config { hash_only: true, ..Config::default() };
+        MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), hash_only).unwrap();
+        assert_eq!(CLONES.with(Cell::get), 0);
+
+        MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        assert_eq!(CLONES.with(Cell::get), leaf_values.len());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let leaf_values: Vec<String> = (0..2100).map(|i| i.to_string()).collect();
+        let paddings = [PaddingStrategy::DuplicateLast, PaddingStrategy::Zero, PaddingStrategy::DuplicateOdd, PaddingStrategy::Unbalanced];
+        for &n in [1, 5, 600, 2100].iter() {
+            for &padding in paddings.iter() {
+                for &(arity, order) in [(2, LeafOrder::Insertion), (2, LeafOrder::ByHash), (4, LeafOrder::Insertion)].iter() {
+                    if arity > 2 && !padding.pads_to_power_of_two() {
+                        continue;
+                    }
+                    let config = Config { padding, arity, order, ..Config::default() };
+                    let mt = MerkleTree::par_from_leaves_with_config(&leaf_values[..n], Sha256::new(), config).unwrap();
+                    let expected = MerkleTree::from_leaves_with_config(&leaf_values[..n], Sha256::new(), config).unwrap();
+                    assert_eq!(mt.levels.len(), expected.levels.len());
+                    for (level, nodes) in mt.levels.iter().enumerate() {
+                        let hashes: Vec<&Hash> = nodes.iter().map(Node::hash).collect();
+                        let expected: Vec<&Hash> = expected.levels[level].iter().map(Node::hash).collect();
+                        assert_eq!(hashes, expected);
+                    }
+                    assert_eq!(mt.leaf(n - 1).unwrap().value(), expected.leaf(n - 1).unwrap().value());
+                }
+            }
+        }
+
+        let config = Config { hash_only: true, ..Config::default() };
+        let mt = MerkleTree::par_from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+        assert!(mt.leaves().all(|leaf| leaf.value().is_none()));
+        assert!(MerkleTree::par_from_leaves(&leaf_values[..0], Sha256::new()).is_err());
+    }
+
+    #[test]
+    fn test_levels_buffer() {
+        let leaf_values: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        let mut mt = MerkleTree::from_leaves(&leaf_values[..5], Sha256::new()).unwrap();
+        // sized for the leaves it was built from: 5 + 3 + 2 + 1 nodes
+        assert_eq!(mt.levels.nodes.len(), 11);
+
+        let mut capacities = vec![];
+        for value in &leaf_values[5..] {
+            mt.add_leaf(value).unwrap();
+            capacities.push(mt.levels.capacity());
+        }
+        capacities.dedup();
+        assert_eq!(capacities, [10, 20, 40, 80, 160]);
+        let expected = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        assert_eq!(mt.root_hash(), expected.root_hash());
+
+        // dropped nodes don't linger in the unused room
+        mt.truncate(3).unwrap();
+        assert_eq!(mt.levels.len(), 3);
+        let in_use: usize = mt.levels.iter().map(<[_]>::len).sum();
+        assert_eq!(mt.levels.nodes.iter().filter(|node| !node.hash.is_empty()).count(), in_use);
+    }
+
+    #[test]
+    fn test_kary_matches_rebuild() {
+        let leaf_values: Vec<String> = (0..40).map(|i| i.to_string()).collect();
+        for &arity in [4, 8, 16].iter() {
+            for &padding in [PaddingStrategy::DuplicateLast, PaddingStrategy::Zero].iter() {
+                let config = Config { padding, arity, ..Config::default() };
+                let mut mt = MerkleTree::from_leaves_with_config(&leaf_values[..1], Sha256::new(), config).unwrap();
+                for n in 2..=leaf_values.len() {
+                    mt.add_leaf(&leaf_values[n - 1]).unwrap();
+
+                    let expected = MerkleTree::from_leaves_with_config(&leaf_values[..n], Sha256::new(), config).unwrap();
+                    assert_eq!(mt.levels.len(), expected.levels.len());
+                    assert_eq!(mt.padding, expected.padding);
+                    assert_eq!(mt.root_hash(), expected.root_hash());
+                }
+
+                mt.update_leaf(17, &String::from("a")).unwrap();
+                mt.remove_leaf(3, Removal::Swap).unwrap();
+                mt.remove_leaf(30, Removal::Shift).unwrap();
+                let values: Vec<String> = mt.leaves().map(|leaf| leaf.value().unwrap().clone()).collect();
+                let expected = MerkleTree::from_leaves_with_config(&values, Sha256::new(), config).unwrap();
+                assert_eq!(mt.root_hash(), expected.root_hash());
+            }
+        }
+    }
+
+    #[test]
+    fn test_kary_levels() {
+        let leaf_values: Vec<String> = (0..17).map(|i| i.to_string()).collect();
+        let config = Config { arity: 4, ..Config::default() };
+        let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+        let widths: Vec<usize> = mt.levels.iter().map(<[_]>::len).collect();
+        assert_eq!(widths, [17, 5, 2, 1]);
+        assert_eq!(mt.count_leaves().unwrap(), 64);
+
+        let config = Config { arity: 1, ..Config::default() };
+        assert!(MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).is_err());
+        let config = Config { arity: 4, padding: PaddingStrategy::Unbalanced, ..Config::default() };
+        assert!(MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).is_err());
+    }
 }
@@ -0,0 +1,129 @@
+use crate::hash::{Hash, HashScheme};
+use crate::hasher::MerkleHasher;
+
+/// How a level with an odd number of nodes is completed.
+///
+/// Every strategy stores the same nodes: only those covering at least one
+/// real leaf. They differ in what stands in for the right sibling of the
+/// last node of an odd level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PaddingStrategy {
+    /// Pad the leaves up to a power of two with copies of the last leaf.
+    /// `[a, b, c]` and `[a, b, c, c]` share a root.
+    #[default]
+    DuplicateLast,
+    /// Pad the leaves up to a power of two with all-zero hashes.
+    Zero,
+    /// Bitcoin style: pair the last node of every odd level with itself.
+    DuplicateOdd,
+    /// RFC 6962 style: promote the last node of every odd level unchanged,
+    /// leaving the tree unbalanced.
+    Unbalanced,
+}
+
+impl PaddingStrategy {
+    /// Whether the leaves are padded up to a power of two, or of the arity
+    /// in a k-ary tree.
+    pub fn pads_to_power_of_two(self) -> bool {
+        match self {
+            PaddingStrategy::DuplicateLast | PaddingStrategy::Zero => true,
+            PaddingStrategy::DuplicateOdd | PaddingStrategy::Unbalanced => false,
+        }
+    }
+
+    /// Number of leaf slots, including padding, of a tree over `leaves`.
+    pub fn padded_len(self, leaves: usize) -> usize {
+        match self {
+            PaddingStrategy::DuplicateLast | PaddingStrategy::Zero => leaves.next_power_of_two(),
+            PaddingStrategy::DuplicateOdd if leaves > 1 => leaves + (leaves & 1),
+            PaddingStrategy::DuplicateOdd | PaddingStrategy::Unbalanced => leaves,
+        }
+    }
+
+    /// Hashes of a fully padded subtree at each level of a tree of `arity`
+    /// whose last leaf hashes to `last_leaf`. Empty for the strategies that
+    /// don't pad.
+    pub(crate) fn padding_hashes<H>(self, last_leaf: &Hash, depth: usize, arity: usize, scheme: HashScheme, hasher: &mut H) -> Vec<Hash>
+        where H: MerkleHasher,
+    {
+        let first = match self {
+            PaddingStrategy::DuplicateLast => last_leaf.clone(),
+            PaddingStrategy::Zero => Hash::zero(last_leaf.len()),
+            PaddingStrategy::DuplicateOdd | PaddingStrategy::Unbalanced => return vec![],
+        };
+
+        let mut hashes = Vec::with_capacity(depth);
+        hashes.push(first);
+        for level in 1..depth {
+            let below = vec![&hashes[level - 1]; arity];
+            let hash = scheme.hash_children(&below, hasher);
+            hashes.push(hash);
+        }
+        hashes
+    }
+
+    /// The right sibling of `node`, the last node of an odd level, or what
+    /// fills each missing child of the last node of a k-ary level. `padding`
+    /// is the padded subtree hash at that level. `None` means the node is
+    /// promoted to the next level as it is.
+    pub(crate) fn missing_sibling(self, node: &Hash, padding: Option<&Hash>) -> Option<Hash> {
+        match self {
+            PaddingStrategy::DuplicateLast | PaddingStrategy::Zero => padding.cloned(),
+            PaddingStrategy::DuplicateOdd => Some(node.clone()),
+            PaddingStrategy::Unbalanced => None,
+        }
+    }
+}
+
+/// Number of levels, leaves and root included, of a tree of `arity` over
+/// `leaves`.
+pub(crate) fn depth(leaves: usize, arity: usize) -> usize {
+    let mut depth = 1;
+    let mut width = 1;
+    while width < leaves {
+        width *= arity;
+        depth += 1;
+    }
+    depth
+}
+
+/// Largest power of two smaller than `leaves`, where an unbalanced tree
+/// over `leaves` is split into its left and right subtrees.
+pub(crate) fn split(leaves: usize) -> usize {
+    leaves.next_power_of_two() / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_padded_len() {
+        assert_eq!(PaddingStrategy::DuplicateLast.padded_len(6), 8);
+        assert_eq!(PaddingStrategy::Zero.padded_len(5), 8);
+        assert_eq!(PaddingStrategy::DuplicateOdd.padded_len(5), 6);
+        assert_eq!(PaddingStrategy::DuplicateOdd.padded_len(1), 1);
+        assert_eq!(PaddingStrategy::Unbalanced.padded_len(5), 5);
+    }
+
+    #[test]
+    fn test_depth() {
+        assert_eq!(depth(1, 2), 1);
+        assert_eq!(depth(2, 2), 2);
+        assert_eq!(depth(5, 2), 4);
+        assert_eq!(depth(8, 2), 4);
+        assert_eq!(depth(4, 4), 2);
+        assert_eq!(depth(5, 4), 3);
+        assert_eq!(depth(17, 16), 3);
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split(2), 1);
+        assert_eq!(split(3), 2);
+        assert_eq!(split(4), 2);
+        assert_eq!(split(5), 4);
+        assert_eq!(split(8), 4);
+        assert_eq!(split(9), 8);
+    }
+}
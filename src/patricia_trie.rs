@@ -0,0 +1,413 @@
+use std::mem;
+
+use crate::hash::Hash;
+use crate::hasher::MerkleHasher;
+use crate::leaf::Leaf;
+use crate::proof::PatriciaProof;
+use crate::rlp::{self, Rlp};
+
+#[derive(Debug, Default)]
+enum TrieNode {
+    #[default]
+    Empty,
+    Leaf {
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: Box<TrieNode>,
+    },
+    Branch {
+        children: Box<[TrieNode; 16]>,
+        value: Option<Vec<u8>>,
+    },
+}
+
+/// Ethereum's Merkle Patricia Trie: a radix-16 trie over the nibbles of
+/// its keys, whose nodes are RLP encoded and referenced by hash. With
+/// Keccak-256 as the hasher (`Sha3::keccak256()`), its root matches the
+/// state, storage, transaction and receipt roots of Ethereum blocks.
+///
+/// The root is recomputed on demand, so `root_hash` and `proof` hash the
+/// whole trie.
+pub struct PatriciaTrie<H>
+    where H: MerkleHasher,
+{
+    hasher: H,
+    root: TrieNode,
+}
+
+impl<H> PatriciaTrie<H>
+    where H: MerkleHasher,
+{
+    pub fn new(hasher: H) -> Self {
+        PatriciaTrie {
+            hasher,
+            root: TrieNode::Empty,
+        }
+    }
+
+    /// A trie keyed by the RLP encoded index of each value, as Ethereum
+    /// uses for the transactions and receipts of a block.
+    pub fn ordered<T>(values: &[T], hasher: H) -> Self
+        where T: Leaf,
+    {
+        let mut trie = Self::new(hasher);
+        for (i, v) in values.iter().enumerate() {
+            let key = Rlp::from_uint(i as u64).encode();
+            trie.insert(&key, &v.leaf_bytes());
+        }
+        trie
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self.root, TrieNode::Empty)
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        let path = to_nibbles(key);
+        let mut path = &path[..];
+        let mut node = &self.root;
+        loop {
+            match node {
+                TrieNode::Empty => return None,
+                TrieNode::Leaf { path: leaf_path, value } => {
+                    return if path == &leaf_path[..] { Some(value) } else { None };
+                }
+                TrieNode::Extension { path: extension, child } => {
+                    if !path.starts_with(extension) {
+                        return None;
+                    }
+                    path = &path[extension.len()..];
+                    node = child;
+                }
+                TrieNode::Branch { children, value } => match path.split_first() {
+                    None => return value.as_deref(),
+                    Some((&nibble, rest)) => {
+                        path = rest;
+                        node = &children[nibble as usize];
+                    }
+                },
+            }
+        }
+    }
+
+    /// Sets the value of `key`. As in Ethereum, an empty value removes the
+    /// key.
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) {
+        if value.is_empty() {
+            self.remove(key);
+            return;
+        }
+        let root = mem::take(&mut self.root);
+        self.root = insert(root, &to_nibbles(key), value.to_vec());
+    }
+
+    /// Removes `key`, returning its value.
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        let root = mem::take(&mut self.root);
+        let (root, removed) = remove(root, &to_nibbles(key));
+        self.root = root;
+        removed
+    }
+
+    /// Keccak-256 of the RLP encoded root node, when hashing with Keccak.
+    pub fn root_hash(&mut self) -> Hash {
+        let encoded = encode(&self.root, &mut self.hasher);
+        keccak(&encoded, &mut self.hasher)
+    }
+
+    /// The RLP encoded nodes on the path to `key`, from the root down, as
+    /// returned by `eth_getProof`. Nodes embedded in their parent are not
+    /// listed separately. Proves the value of `key`, or that it is absent.
+    pub fn proof(&mut self, key: &[u8]) -> PatriciaProof {
+        let path = to_nibbles(key);
+        let mut path = &path[..];
+        let mut node = &self.root;
+        let mut nodes = vec![encode(node, &mut self.hasher)];
+        loop {
+            node = match node {
+                TrieNode::Empty | TrieNode::Leaf { .. } => break,
+                TrieNode::Extension { path: extension, child } => {
+                    if !path.starts_with(extension) {
+                        break;
+                    }
+                    path = &path[extension.len()..];
+                    child
+                }
+                TrieNode::Branch { children, .. } => match path.split_first() {
+                    None => break,
+                    Some((&nibble, rest)) => {
+                        path = rest;
+                        &children[nibble as usize]
+                    }
+                },
+            };
+
+            let encoded = encode(node, &mut self.hasher);
+            if encoded.len() >= 32 {
+                nodes.push(encoded);
+            }
+        }
+        PatriciaProof::new(key.to_vec(), nodes)
+    }
+}
+
+fn encode<H>(node: &TrieNode, hasher: &mut H) -> Vec<u8>
+    where H: MerkleHasher,
+{
+    match node {
+        TrieNode::Empty => rlp::encode_bytes(&[]),
+        TrieNode::Leaf { path, value } => {
+            rlp::encode_list(&[rlp::encode_bytes(&hex_prefix(path, true)), rlp::encode_bytes(value)])
+        }
+        TrieNode::Extension { path, child } => {
+            let child = reference(child, hasher);
+            rlp::encode_list(&[rlp::encode_bytes(&hex_prefix(path, false)), child])
+        }
+        TrieNode::Branch { children, value } => {
+            let mut items: Vec<Vec<u8>> = children.iter().map(|child| reference(child, hasher)).collect();
+            items.push(rlp::encode_bytes(value.as_deref().unwrap_or(&[])));
+            rlp::encode_list(&items)
+        }
+    }
+}
+
+// how a parent refers to `node`: embedded when its encoding is shorter than
+// a hash, by hash otherwise
+fn reference<H>(node: &TrieNode, hasher: &mut H) -> Vec<u8>
+    where H: MerkleHasher,
+{
+    let encoded = encode(node, hasher);
+    if encoded.len() < 32 {
+        encoded
+    } else {
+        rlp::encode_bytes(keccak(&encoded, hasher).as_bytes())
+    }
+}
+
+fn insert(node: TrieNode, path: &[u8], value: Vec<u8>) -> TrieNode {
+    match node {
+        TrieNode::Empty => TrieNode::Leaf { path: path.to_vec(), value },
+        TrieNode::Leaf { path: leaf_path, value: leaf_value } => {
+            if leaf_path == path {
+                return TrieNode::Leaf { path: leaf_path, value };
+            }
+            let common = common_prefix(&leaf_path, path);
+            let branch = insert(TrieNode::Empty, &leaf_path[common..], leaf_value);
+            let branch = insert(into_branch(branch), &path[common..], value);
+            with_prefix(&path[..common], branch)
+        }
+        TrieNode::Extension { path: extension, child } => {
+            let common = common_prefix(&extension, path);
+            if common == extension.len() {
+                let child = insert(*child, &path[common..], value);
+                return TrieNode::Extension { path: extension, child: Box::new(child) };
+            }
+
+            let mut children: Box<[TrieNode; 16]> = Box::default();
+            children[extension[common] as usize] = with_prefix(&extension[common + 1..], *child);
+            let branch = TrieNode::Branch { children, value: None };
+            let branch = insert(branch, &path[common..], value);
+            with_prefix(&path[..common], branch)
+        }
+        TrieNode::Branch { mut children, value: branch_value } => match path.split_first() {
+            None => TrieNode::Branch { children, value: Some(value) },
+            Some((&nibble, rest)) => {
+                let child = mem::take(&mut children[nibble as usize]);
+                children[nibble as usize] = insert(child, rest, value);
+                TrieNode::Branch { children, value: branch_value }
+            }
+        },
+    }
+}
+
+fn remove(node: TrieNode, path: &[u8]) -> (TrieNode, Option<Vec<u8>>) {
+    match node {
+        TrieNode::Empty => (TrieNode::Empty, None),
+        TrieNode::Leaf { path: leaf_path, value } => {
+            if leaf_path == path {
+                (TrieNode::Empty, Some(value))
+            } else {
+                (TrieNode::Leaf { path: leaf_path, value }, None)
+            }
+        }
+        TrieNode::Extension { path: extension, child } => {
+            if !path.starts_with(&extension) {
+                return (TrieNode::Extension { path: extension, child }, None);
+            }
+            let (child, removed) = remove(*child, &path[extension.len()..]);
+            (with_prefix(&extension, child), removed)
+        }
+        TrieNode::Branch { mut children, value } => {
+            let (value, removed) = match path.split_first() {
+                None => (None, value),
+                Some((&nibble, rest)) => {
+                    let child = mem::take(&mut children[nibble as usize]);
+                    let (child, removed) = remove(child, rest);
+                    children[nibble as usize] = child;
+                    (value, removed)
+                }
+            };
+            (collapse(children, value), removed)
+        }
+    }
+}
+
+// a branch with fewer than two entries left is replaced by what it holds
+fn collapse(mut children: Box<[TrieNode; 16]>, value: Option<Vec<u8>>) -> TrieNode {
+    let mut occupied = children.iter().enumerate().filter(|(_, child)| !matches!(child, TrieNode::Empty));
+    match (occupied.next().map(|(i, _)| i), occupied.next(), value) {
+        (None, _, None) => TrieNode::Empty,
+        (None, _, Some(value)) => TrieNode::Leaf { path: vec![], value },
+        (Some(i), None, None) => {
+            let child = mem::take(&mut children[i]);
+            with_prefix(&[i as u8], child)
+        }
+        (_, _, value) => TrieNode::Branch { children, value },
+    }
+}
+
+// a branch holding `node` where it sits, a leaf's value in the branch itself
+fn into_branch(node: TrieNode) -> TrieNode {
+    let mut children: Box<[TrieNode; 16]> = Box::default();
+    match node {
+        TrieNode::Leaf { path, value } if path.is_empty() => TrieNode::Branch { children, value: Some(value) },
+        TrieNode::Leaf { path, value } => {
+            children[path[0] as usize] = TrieNode::Leaf { path: path[1..].to_vec(), value };
+            TrieNode::Branch { children, value: None }
+        }
+        node => node,
+    }
+}
+
+// `node` below the nibbles `prefix`, merged into it when it has a path
+fn with_prefix(prefix: &[u8], node: TrieNode) -> TrieNode {
+    if prefix.is_empty() {
+        return node;
+    }
+    match node {
+        TrieNode::Empty => TrieNode::Empty,
+        TrieNode::Leaf { path, value } => TrieNode::Leaf { path: [prefix, &path].concat(), value },
+        TrieNode::Extension { path, child } => TrieNode::Extension { path: [prefix, &path].concat(), child },
+        branch => TrieNode::Extension { path: prefix.to_vec(), child: Box::new(branch) },
+    }
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+pub(crate) fn keccak<H>(bytes: &[u8], hasher: &mut H) -> Hash
+    where H: MerkleHasher,
+{
+    H::into_hash(hasher.hash(&[bytes]))
+}
+
+pub(crate) fn to_nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|b| vec![b >> 4, b & 0x0f]).collect()
+}
+
+/// Hex-prefix encoding of a node path: a flag nibble telling leaves from
+/// extensions and odd from even lengths, then the nibbles packed in bytes.
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 };
+    let mut out = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if nibbles.len() & 1 == 1 {
+        out.push((flag + 1) << 4 | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        out.push(flag << 4);
+        nibbles
+    };
+    out.extend(rest.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+    out
+}
+
+/// Decodes a hex-prefix encoded path into its nibbles and whether it is a
+/// leaf's.
+pub(crate) fn from_hex_prefix(bytes: &[u8]) -> Result<(Vec<u8>, bool), &'static str> {
+    let first = *bytes.first().ok_or("Empty node path")?;
+    let flag = first >> 4;
+    if flag > 3 || (flag & 1 == 0 && first & 0x0f != 0) {
+        return Err("Invalid node path");
+    }
+
+    let mut nibbles = if flag & 1 == 1 { vec![first & 0x0f] } else { vec![] };
+    nibbles.extend(to_nibbles(&bytes[1..]));
+    Ok((nibbles, flag >= 2))
+}
+
+#[cfg(all(test, feature = "rust-crypto"))]
+mod tests {
+    use crypto::sha3::Sha3;
+    use super::*;
+
+    fn trie(pairs: &[(&str, &str)]) -> PatriciaTrie<Sha3> {
+        let mut trie = PatriciaTrie::new(Sha3::keccak256());
+        for (key, value) in pairs {
+            trie.insert(key.as_bytes(), value.as_bytes());
+        }
+        trie
+    }
+
+    #[test]
+    fn test_empty_root() {
+        let mut trie = trie(&[]);
+        assert!(trie.is_empty());
+        assert_eq!(trie.root_hash().to_hex(), "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+    }
+
+    #[test]
+    fn test_root_hash() {
+        let mut dogs = trie(&[("doe", "reindeer"), ("dog", "puppy"), ("dogglesworth", "cat")]);
+        assert_eq!(dogs.root_hash().to_hex(), "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3");
+
+        let mut words = trie(&[("do", "verb"), ("horse", "stallion"), ("doge", "coin"), ("dog", "puppy")]);
+        assert_eq!(words.root_hash().to_hex(), "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84");
+        assert_eq!(words.get(b"doge"), Some(&b"coin"[..]));
+        assert_eq!(words.get(b"dogs"), None);
+
+        let mut reversed = trie(&[("dog", "puppy"), ("doge", "coin"), ("horse", "stallion"), ("do", "verb")]);
+        assert_eq!(reversed.root_hash(), words.root_hash());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut words = trie(&[("do", "verb"), ("horse", "stallion"), ("doge", "coin"), ("dog", "puppy")]);
+        let mut dogs = trie(&[("do", "verb"), ("dog", "puppy")]);
+
+        assert_eq!(words.remove(b"horse"), Some(b"stallion".to_vec()));
+        assert_eq!(words.remove(b"horse"), None);
+        words.insert(b"doge", b"");
+        assert_eq!(words.root_hash(), dogs.root_hash());
+
+        words.remove(b"do");
+        words.remove(b"dog");
+        assert!(words.is_empty());
+    }
+
+    // Regression root, as computed by this crate; the keys are checked by
+    // looking up index 128, whose RLP encoding is 0x81 0x80.
+    #[test]
+    fn test_ordered() {
+        let values: Vec<Vec<u8>> = (0..200u32).map(|i| i.to_be_bytes().repeat(10)).collect();
+        let mut trie = PatriciaTrie::ordered(&values, Sha3::keccak256());
+        assert_eq!(trie.root_hash().to_hex(), "4d266e8f7743938332931abdfb62fcf929546b1c1561df83e8b5d02104706386");
+        assert_eq!(trie.get(&[0x81, 0x80]), Some(&values[128][..]));
+    }
+
+    #[test]
+    fn test_hex_prefix() {
+        assert_eq!(hex_prefix(&[1, 2, 3, 4, 5], false), [0x11, 0x23, 0x45]);
+        assert_eq!(hex_prefix(&[0, 1, 2, 3, 4, 5], false), [0x00, 0x01, 0x23, 0x45]);
+        assert_eq!(hex_prefix(&[0, 15, 1, 12, 11, 8], true), [0x20, 0x0f, 0x1c, 0xb8]);
+        assert_eq!(hex_prefix(&[15, 1, 12, 11, 8], true), [0x3f, 0x1c, 0xb8]);
+
+        assert_eq!(from_hex_prefix(&[0x3f, 0x1c, 0xb8]), Ok((vec![15, 1, 12, 11, 8], true)));
+        assert_eq!(from_hex_prefix(&[0x00, 0x01]), Ok((vec![0, 1], false)));
+        assert!(from_hex_prefix(&[0x05, 0x01]).is_err());
+        assert!(from_hex_prefix(&[0x40]).is_err());
+    }
+}
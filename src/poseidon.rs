@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use light_poseidon::PoseidonHasher;
+
+use crate::hash::Hash;
+use crate::hasher::MerkleHasher;
+use crate::leaf::Leaf;
+
+/// An element of the BN254 scalar field, held as its canonical 32-byte
+/// big-endian encoding. Leaves of a `MerkleTree` hashed with `Poseidon`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FieldElement([u8; 32]);
+
+impl FieldElement {
+    pub fn from_u64(value: u64) -> Self {
+        FieldElement::from(Fr::from(value))
+    }
+
+    /// Parses a big-endian integer of at most 32 bytes, which must be less
+    /// than the field modulus. The empty slice is zero.
+    pub fn from_be_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        if bytes.len() > 32 {
+            return Err("Field element longer than 32 bytes");
+        }
+        let mut padded = [0u8; 32];
+        padded[32 - bytes.len()..].copy_from_slice(bytes);
+
+        let element = FieldElement::from(Fr::from_be_bytes_mod_order(&padded));
+        if element.0 != padded {
+            return Err("Field element not less than the modulus");
+        }
+        Ok(element)
+    }
+
+    /// The element a tree hashed with `Poseidon` stores as `hash`.
+    pub fn from_hash(hash: &Hash) -> Result<Self, &'static str> {
+        FieldElement::from_be_bytes(hash.as_bytes())
+    }
+
+    pub fn to_hash(&self) -> Hash {
+        Hash::new(&self.0)
+    }
+}
+
+impl From<Fr> for FieldElement {
+    fn from(element: Fr) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&element.into_bigint().to_bytes_be());
+        FieldElement(bytes)
+    }
+}
+
+impl From<FieldElement> for Fr {
+    fn from(element: FieldElement) -> Self {
+        Fr::from_be_bytes_mod_order(&element.0)
+    }
+}
+
+impl Leaf for FieldElement {
+    fn leaf_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn write_field(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.0);
+    }
+}
+
+/// The Poseidon hash over BN254 with the parameters of circomlib, so that
+/// roots and proofs can be checked inside circom circuits.
+///
+/// Each part passed to `hash` is one input, read as a big-endian field
+/// element: leaves hash as `Poseidon([leaf])` and internal nodes as
+/// `Poseidon([left, right])`. Any number of inputs from 1 to 12 is
+/// accepted, covering nodes of arity 4 as well as 2.
+///
+/// Circuits and tools such as zk-kit's incremental merkle tree take the
+/// leaves as they are, without hashing them first; build with
+/// `MerkleTree::from_hashes` to get the same roots for full trees.
+///
+/// # Panics
+///
+/// Hashing panics on more than 12 inputs, or on an input that is not a
+/// field element. Trees and proofs check leaves with `check_input` first,
+/// and return an error for a leaf that is not a `FieldElement` instead.
+#[derive(Default)]
+pub struct Poseidon {
+    // one permutation per number of inputs, built on first use
+    permutations: HashMap<usize, light_poseidon::Poseidon<Fr>>,
+}
+
+impl Poseidon {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Clone for Poseidon {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Poseidon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Poseidon")
+    }
+}
+
+impl MerkleHasher for Poseidon {
+    type Output = [u8; 32];
+
+    fn output_len(&self) -> usize {
+        32
+    }
+
+    fn hash(&mut self, parts: &[&[u8]]) -> [u8; 32] {
+        let inputs: Vec<Fr> = parts.iter()
+            .map(|part| FieldElement::from_be_bytes(part).expect("Poseidon input is not a field element").into())
+            .collect();
+        let permutation = self.permutations.entry(inputs.len())
+            .or_insert_with(|| light_poseidon::Poseidon::<Fr>::new_circom(inputs.len()).expect("Poseidon takes 1 to 12 inputs"));
+        FieldElement::from(permutation.hash(&inputs).expect("Poseidon takes 1 to 12 inputs")).0
+    }
+
+    fn check_input(&self, data: &[u8]) -> Result<(), &'static str> {
+        FieldElement::from_be_bytes(data).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poseidon(inputs: &[u64]) -> String {
+        let elements: Vec<FieldElement> = inputs.iter().map(|&i| FieldElement::from_u64(i)).collect();
+        let parts: Vec<&[u8]> = elements.iter().map(|e| &e.0[..]).collect();
+        Hash::new(&Poseidon::new().hash(&parts)).to_hex()
+    }
+
+    // Test vectors from circomlibjs.
+    #[test]
+    fn test_poseidon() {
+        assert_eq!(poseidon(&[1]), "29176100eaa962bdc1fe6c654d6a3c130e96a4d1168b33848b897dc502820133");
+        assert_eq!(poseidon(&[1, 2]), "115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a");
+        assert_eq!(poseidon(&[1, 2, 3, 4]), "299c867db6c1fdd79dcefa40e4510b9837e60ebb1ce0663dbaa525df65250465");
+    }
+
+    #[test]
+    fn test_field_element() {
+        let one = FieldElement::from_u64(1);
+        assert_eq!(FieldElement::from_be_bytes(&[1]), Ok(one));
+        assert_eq!(FieldElement::from_hash(&one.to_hash()), Ok(one));
+        assert_eq!(FieldElement::from_be_bytes(&[]), Ok(FieldElement::default()));
+
+        let modulus = Hash::from_hex("30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001").unwrap();
+        assert!(FieldElement::from_hash(&modulus).is_err());
+        assert!(FieldElement::from_be_bytes(&[1; 33]).is_err());
+    }
+}
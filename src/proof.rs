@@ -0,0 +1,1312 @@
+use crate::hash::{Hash, HashScheme};
+use crate::hasher::MerkleHasher;
+use crate::leaf::Leaf;
+use crate::merkle_tree::{Config, LeafOrder};
+use crate::padding::{self, PaddingStrategy};
+use crate::patricia_trie;
+use crate::rlp::Rlp;
+use crate::sparse_merkle_tree::{self, KEY_BITS};
+
+/// Side of the path a sibling hash sits on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Position {
+    Left,
+    Right,
+}
+
+/// An inclusion proof for a single leaf: the sibling hashes from the
+/// leaf up to (but excluding) the root, each tagged with its side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof {
+    index: usize,
+    path: Vec<(Position, Hash)>,
+}
+
+impl MerkleProof {
+    pub fn new(index: usize, path: Vec<(Position, Hash)>) -> Self {
+        MerkleProof {
+            index,
+            path,
+        }
+    }
+
+    /// Index of the leaf this proof was generated for.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn path(&self) -> &[(Position, Hash)] {
+        &self.path
+    }
+
+    /// Rebuilds a proof from an RFC 6962 audit path, which only lists the
+    /// sibling hashes: their sides follow from the leaf index and the tree
+    /// size (RFC 9162, section 2.1.3.2).
+    pub fn from_audit_path(index: usize, tree_size: usize, hashes: Vec<Hash>) -> Result<Self, &'static str> {
+        if index >= tree_size {
+            return Err("Leaf index out of bounds");
+        }
+
+        let mut path = Vec::with_capacity(hashes.len());
+        let mut node = index;
+        let mut last_node = tree_size - 1;
+        for hash in hashes {
+            if last_node == 0 {
+                return Err("Audit path is too long");
+            }
+            if node & 1 == 1 || node == last_node {
+                path.push((Position::Left, hash));
+                if node & 1 == 0 {
+                    while node & 1 == 0 && node != 0 {
+                        node >>= 1;
+                        last_node >>= 1;
+                    }
+                }
+            } else {
+                path.push((Position::Right, hash));
+            }
+            node >>= 1;
+            last_node >>= 1;
+        }
+
+        if last_node != 0 {
+            return Err("Audit path is too short");
+        }
+        Ok(MerkleProof::new(index, path))
+    }
+
+    /// The sibling hashes alone, as in an RFC 6962 audit path.
+    pub fn audit_path(&self) -> Vec<Hash> {
+        self.path.iter().map(|(_, hash)| hash.clone()).collect()
+    }
+
+    /// Folds `leaf` with the sibling hashes, returning the root it implies
+    /// for an `Unprefixed` tree. Fails when the hasher rejects the leaf or
+    /// a sibling, see `MerkleHasher::check_input`.
+    pub fn root<H, T>(&self, leaf: &T, hasher: &mut H) -> Result<Hash, &'static str>
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        self.root_with_scheme(leaf, hasher, HashScheme::Unprefixed)
+    }
+
+    pub fn root_with_scheme<H, T>(&self, leaf: &T, hasher: &mut H, scheme: HashScheme) -> Result<Hash, &'static str>
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        check_hashes(self.path.iter().map(|(_, sibling)| sibling), hasher)?;
+        let mut hash = scheme.try_hash_leaf(leaf, hasher)?;
+        for (position, sibling) in &self.path {
+            hash = match position {
+                Position::Left => scheme.hash_internal(sibling, &hash, hasher),
+                Position::Right => scheme.hash_internal(&hash, sibling, hasher),
+            };
+        }
+        Ok(hash)
+    }
+
+    /// Checks that `leaf` is included in the `Unprefixed` tree with the
+    /// given `root`.
+    pub fn verify<H, T>(&self, root: &Hash, leaf: &T, hasher: &mut H) -> bool
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        self.verify_with_scheme(root, leaf, hasher, HashScheme::Unprefixed)
+    }
+
+    /// Checks that `leaf` is included in the tree with the given `root`,
+    /// hashed with `scheme`. The scheme is the verifier's choice and is
+    /// deliberately not carried by the proof.
+    pub fn verify_with_scheme<H, T>(&self, root: &Hash, leaf: &T, hasher: &mut H, scheme: HashScheme) -> bool
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        self.root_with_scheme(leaf, hasher, scheme).as_ref() == Ok(root)
+    }
+}
+
+/// An inclusion proof for a single leaf of a tree of any arity: for each
+/// level from the leaf up, the position of the path's node among its
+/// siblings, and the sibling hashes from left to right.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KaryProof {
+    index: usize,
+    path: Vec<(usize, Vec<Hash>)>,
+}
+
+impl KaryProof {
+    pub fn new(index: usize, path: Vec<(usize, Vec<Hash>)>) -> Self {
+        KaryProof {
+            index,
+            path,
+        }
+    }
+
+    /// Index of the leaf this proof was generated for.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn path(&self) -> &[(usize, Vec<Hash>)] {
+        &self.path
+    }
+
+    /// Folds `leaf` with the sibling hashes, returning the root it implies
+    /// for an `Unprefixed` tree. A node without siblings is promoted as it
+    /// is.
+    pub fn root<H, T>(&self, leaf: &T, hasher: &mut H) -> Result<Hash, &'static str>
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        self.root_with_scheme(leaf, hasher, HashScheme::Unprefixed)
+    }
+
+    pub fn root_with_scheme<H, T>(&self, leaf: &T, hasher: &mut H, scheme: HashScheme) -> Result<Hash, &'static str>
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        check_hashes(self.path.iter().flat_map(|(_, siblings)| siblings), hasher)?;
+        let mut hash = scheme.try_hash_leaf(leaf, hasher)?;
+        for (position, siblings) in &self.path {
+            if *position > siblings.len() {
+                return Err("Position out of range of the siblings");
+            }
+            if siblings.is_empty() {
+                continue;
+            }
+
+            let mut children: Vec<&Hash> = siblings.iter().collect();
+            children.insert(*position, &hash);
+            hash = scheme.hash_children(&children, hasher);
+        }
+        Ok(hash)
+    }
+
+    /// Checks that `leaf` is included in the `Unprefixed` tree with the
+    /// given `root`.
+    pub fn verify<H, T>(&self, root: &Hash, leaf: &T, hasher: &mut H) -> bool
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        self.verify_with_scheme(root, leaf, hasher, HashScheme::Unprefixed)
+    }
+
+    pub fn verify_with_scheme<H, T>(&self, root: &Hash, leaf: &T, hasher: &mut H, scheme: HashScheme) -> bool
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        self.root_with_scheme(leaf, hasher, scheme).as_ref() == Ok(root)
+    }
+}
+
+/// An inclusion proof for several leaves of the same tree. Only the
+/// sibling hashes that cannot be derived from the proven leaves are
+/// carried, in the order the verifier consumes them: level by level from
+/// the leaves up, left to right within a level. The tree's size and
+/// padding strategy are carried along for reference only: the verifier
+/// supplies its own, since they determine the tree's shape.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiProof {
+    leaves: usize,
+    padding: PaddingStrategy,
+    indices: Vec<usize>,
+    hashes: Vec<Hash>,
+}
+
+impl MultiProof {
+    pub fn new(leaves: usize, padding: PaddingStrategy, indices: Vec<usize>, hashes: Vec<Hash>) -> Self {
+        MultiProof {
+            leaves,
+            padding,
+            indices,
+            hashes,
+        }
+    }
+
+    /// Number of leaves in the tree, excluding padding.
+    pub fn leaves(&self) -> usize {
+        self.leaves
+    }
+
+    pub fn padding(&self) -> PaddingStrategy {
+        self.padding
+    }
+
+    /// Sorted indices of the proven leaves.
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    pub fn hashes(&self) -> &[Hash] {
+        &self.hashes
+    }
+
+    /// Reconstructs the root of the tree with `size` leaves and `config`
+    /// from `leaves`, given in the order of `indices()`, and the auxiliary
+    /// hashes. The size and padding come from the verifier: a proof built
+    /// for another shape of the tree is rejected.
+    pub fn root<H, T>(&self, leaves: &[T], size: usize, config: &Config, hasher: &mut H) -> Result<Hash, &'static str>
+        where H: MerkleHasher,
+              T: Leaf,
+    {
+        if config.arity != 2 {
+            return Err("Multiproofs require a binary tree");
+        }
+        if self.leaves != size || self.padding != config.padding {
+            return Err("Proof does not match the shape of the tree");
+        }
+        if self.indices.is_empty() || leaves.len() != self.indices.len() {
+            return Err("Proof does not match the number of leaves");
+        }
+        if self.indices.windows(2).any(|w| w[0] >= w[1]) || self.indices[self.indices.len() - 1] >= size {
+            return Err("Proof indices are not valid");
+        }
+
+        let scheme = config.scheme;
+        check_hashes(&self.hashes, hasher)?;
+        let mut known = self.indices.iter()
+            .zip(leaves)
+            .map(|(&i, leaf)| Ok((i, scheme.try_hash_leaf(leaf, hasher)?)))
+            .collect::<Result<Vec<(usize, Hash)>, &'static str>>()?;
+        let mut hashes = self.hashes.iter();
+        let mut width = size;
+        while width > 1 {
+            let mut parents = vec![];
+            let mut k = 0;
+            while k < known.len() {
+                let (i, ref hash) = known[k];
+                let parent = match known.get(k + 1) {
+                    Some((j, right)) if i & 1 == 0 && *j == i + 1 => {
+                        k += 2;
+                        scheme.hash_internal(hash, right, hasher)
+                    }
+                    _ if i ^ 1 >= width => {
+                        k += 1;
+                        let sibling = if config.padding.pads_to_power_of_two() {
+                            Some(hashes.next().ok_or("Proof is missing hashes")?.clone())
+                        } else {
+                            config.padding.missing_sibling(hash, None)
+                        };
+                        match sibling {
+                            Some(sibling) => scheme.hash_internal(hash, &sibling, hasher),
+                            None => hash.clone(),
+                        }
+                    }
+                    _ => {
+                        k += 1;
+                        let sibling = hashes.next().ok_or("Proof is missing hashes")?;
+                        if i & 1 == 0 {
+                            scheme.hash_internal(hash, sibling, hasher)
+                        } else {
+                            scheme.hash_internal(sibling, hash, hasher)
+                        }
+                    }
+                };
+                parents.push((i / 2, parent));
+            }
+
+            known = parents;
+            width = width.div_ceil(2);
+        }
+
+        if hashes.next().is_some() {
+            return Err("Proof has unused hashes");
+        }
+        Ok(known.swap_remove(0).1)
+    }
+
+    /// Checks that `leaves` are all included in the tree with the given
+    /// `root`, `size` leaves and `config`.
+    pub fn verify<H, T>(&self, root: &Hash, leaves: &[T], size: usize, config: &Config, hasher: &mut H) -> bool
+        where H: MerkleHasher,
+              T: Leaf,
+    {
+        self.root(leaves, size, config, hasher).as_ref() == Ok(root)
+    }
+}
+
+/// A proof that a tree is an append-only extension of an earlier version
+/// of itself (RFC 9162, section 2.1.4), valid for unbalanced trees.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsistencyProof {
+    old_size: usize,
+    new_size: usize,
+    hashes: Vec<Hash>,
+}
+
+impl ConsistencyProof {
+    pub fn new(old_size: usize, new_size: usize, hashes: Vec<Hash>) -> Self {
+        ConsistencyProof {
+            old_size,
+            new_size,
+            hashes,
+        }
+    }
+
+    pub fn old_size(&self) -> usize {
+        self.old_size
+    }
+
+    pub fn new_size(&self) -> usize {
+        self.new_size
+    }
+
+    pub fn hashes(&self) -> &[Hash] {
+        &self.hashes
+    }
+
+    /// Checks that the `Unprefixed` tree with `new_root` extends the one
+    /// with `old_root`.
+    pub fn verify<H>(&self, old_root: &Hash, new_root: &Hash, hasher: &mut H) -> bool
+        where H: MerkleHasher,
+    {
+        self.verify_with_scheme(old_root, new_root, hasher, HashScheme::Unprefixed)
+    }
+
+    pub fn verify_with_scheme<H>(&self, old_root: &Hash, new_root: &Hash, hasher: &mut H, scheme: HashScheme) -> bool
+        where H: MerkleHasher,
+    {
+        if self.old_size == 0 || self.old_size > self.new_size {
+            return false;
+        }
+        if check_hashes(&self.hashes, hasher).is_err() {
+            return false;
+        }
+        if self.old_size == self.new_size {
+            return self.hashes.is_empty() && old_root == new_root;
+        }
+
+        // a power of two sized old tree is a subtree of the new one, and
+        // its root is left out of the proof
+        let mut hashes = self.hashes.iter();
+        let first = if self.old_size.is_power_of_two() {
+            old_root
+        } else {
+            match hashes.next() {
+                Some(hash) => hash,
+                None => return false,
+            }
+        };
+
+        let mut node = self.old_size - 1;
+        let mut last_node = self.new_size - 1;
+        while node & 1 == 1 {
+            node >>= 1;
+            last_node >>= 1;
+        }
+
+        let mut old_hash = first.clone();
+        let mut new_hash = first.clone();
+        for hash in hashes {
+            if last_node == 0 {
+                return false;
+            }
+            if node & 1 == 1 || node == last_node {
+                old_hash = scheme.hash_internal(hash, &old_hash, hasher);
+                new_hash = scheme.hash_internal(hash, &new_hash, hasher);
+                while node & 1 == 0 && node != 0 {
+                    node >>= 1;
+                    last_node >>= 1;
+                }
+            } else {
+                new_hash = scheme.hash_internal(&new_hash, hash, hasher);
+            }
+            node >>= 1;
+            last_node >>= 1;
+        }
+
+        last_node == 0 && old_hash == *old_root && new_hash == *new_root
+    }
+}
+
+/// A proof that a value is not a leaf of a sorted tree: inclusion proofs
+/// for the adjacent leaves it would sit between, each with the leaf's
+/// bytes so the verifier can check the order. Either neighbour is missing
+/// when the value would come first or last. The tree's size, padding
+/// strategy and order are carried along for reference only: the verifier
+/// supplies its own, and each path must have the shape they imply for its
+/// index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NonMembershipProof {
+    leaves: usize,
+    padding: PaddingStrategy,
+    order: LeafOrder,
+    left: Option<(Vec<u8>, MerkleProof)>,
+    right: Option<(Vec<u8>, MerkleProof)>,
+}
+
+impl NonMembershipProof {
+    pub fn new(
+        leaves: usize,
+        padding: PaddingStrategy,
+        order: LeafOrder,
+        left: Option<(Vec<u8>, MerkleProof)>,
+        right: Option<(Vec<u8>, MerkleProof)>,
+    ) -> Self {
+        NonMembershipProof {
+            leaves,
+            padding,
+            order,
+            left,
+            right,
+        }
+    }
+
+    pub fn leaves(&self) -> usize {
+        self.leaves
+    }
+
+    pub fn padding(&self) -> PaddingStrategy {
+        self.padding
+    }
+
+    pub fn order(&self) -> LeafOrder {
+        self.order
+    }
+
+    /// The leaf just before the missing value, and its inclusion proof.
+    pub fn left(&self) -> Option<(&[u8], &MerkleProof)> {
+        self.left.as_ref().map(|(bytes, proof)| (bytes.as_slice(), proof))
+    }
+
+    /// The leaf just after the missing value, and its inclusion proof.
+    pub fn right(&self) -> Option<(&[u8], &MerkleProof)> {
+        self.right.as_ref().map(|(bytes, proof)| (bytes.as_slice(), proof))
+    }
+
+    /// Checks that `value` is not a leaf of the tree with the given `root`,
+    /// `leaves` leaves and `config`. The size, padding and order come from
+    /// the verifier rather than the prover: a proof built for another
+    /// shape or order of the tree is rejected.
+    pub fn verify<H, T>(&self, root: &Hash, value: &T, leaves: usize, config: &Config, hasher: &mut H) -> bool
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        if !config.order.is_sorted() || config.arity != 2 {
+            return false;
+        }
+        if self.leaves != leaves || self.padding != config.padding || self.order != config.order {
+            return false;
+        }
+        let scheme = config.scheme;
+
+        let adjacent = match (&self.left, &self.right) {
+            (Some((_, left)), Some((_, right))) => right.index() == left.index() + 1,
+            (None, Some((_, right))) => right.index() == 0,
+            (Some((_, left)), None) => left.index() + 1 == self.leaves,
+            (None, None) => false,
+        };
+        if !adjacent {
+            return false;
+        }
+
+        let hash = match scheme.try_hash_leaf(value, hasher) {
+            Ok(hash) => hash,
+            Err(_) => return false,
+        };
+        let key = self.order.key(value, &hash);
+        let neighbours = [(&self.left, std::cmp::Ordering::Less), (&self.right, std::cmp::Ordering::Greater)];
+        for (neighbour, expected) in neighbours.iter() {
+            if let Some((bytes, proof)) = neighbour {
+                let leaf = match scheme.try_hash_leaf(bytes.as_slice(), hasher) {
+                    Ok(leaf) => leaf,
+                    Err(_) => return false,
+                };
+                if self.order.key(bytes, &leaf).cmp(&key) != *expected {
+                    return false;
+                }
+                if self.implied_root(&leaf, proof, hasher, scheme).as_ref() != Some(root) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    // the root `proof` implies for a leaf hashing to `leaf`, or `None` when
+    // the path doesn't have the shape of its index in a tree of
+    // `self.leaves`, so that the index can be trusted
+    fn implied_root<H>(&self, leaf: &Hash, proof: &MerkleProof, hasher: &mut H, scheme: HashScheme) -> Option<Hash>
+        where H: MerkleHasher,
+    {
+        let mut index = proof.index();
+        if index >= self.leaves {
+            return None;
+        }
+        check_hashes(proof.path().iter().map(|(_, sibling)| sibling), hasher).ok()?;
+        // duplicated padding is only known from the last leaf itself
+        let padding = match self.padding {
+            PaddingStrategy::DuplicateLast if index + 1 != self.leaves => vec![],
+            _ => self.padding.padding_hashes(leaf, padding::depth(self.leaves, 2), 2, scheme, hasher),
+        };
+
+        let mut hash = leaf.clone();
+        let mut path = proof.path().iter();
+        let mut width = self.leaves;
+        let mut level = 0;
+        while width > 1 {
+            if index & 1 == 1 {
+                match path.next() {
+                    Some((Position::Left, sibling)) => hash = scheme.hash_internal(sibling, &hash, hasher),
+                    _ => return None,
+                }
+            } else if index + 1 < width || self.padding != PaddingStrategy::Unbalanced {
+                let sibling = match path.next() {
+                    Some((Position::Right, sibling)) => sibling,
+                    _ => return None,
+                };
+                if index + 1 == width {
+                    let expected = self.padding.missing_sibling(&hash, padding.get(level));
+                    if expected.is_some_and(|expected| expected != *sibling) {
+                        return None;
+                    }
+                }
+                hash = scheme.hash_internal(&hash, sibling, hasher);
+            }
+            index >>= 1;
+            width = width.div_ceil(2);
+            level += 1;
+        }
+
+        if path.next().is_some() {
+            return None;
+        }
+        Some(hash)
+    }
+}
+
+/// A proof of the value of one key of a `SparseMerkleTree`, or of its
+/// absence. Siblings that are empty subtrees are left out; the others are
+/// tagged with their height above the leaves, lowest first, and the
+/// verifier recomputes the defaults for the gaps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SparseMerkleProof {
+    key: [u8; 32],
+    siblings: Vec<(usize, Hash)>,
+}
+
+impl SparseMerkleProof {
+    pub fn new(key: [u8; 32], siblings: Vec<(usize, Hash)>) -> Self {
+        SparseMerkleProof {
+            key,
+            siblings,
+        }
+    }
+
+    pub fn key(&self) -> &[u8; 32] {
+        &self.key
+    }
+
+    pub fn siblings(&self) -> &[(usize, Hash)] {
+        &self.siblings
+    }
+
+    /// The root implied by `value` at the proof's key, `None` standing for
+    /// an absent key, in an `Unprefixed` tree.
+    pub fn root<H, T>(&self, value: Option<&T>, hasher: &mut H) -> Result<Hash, &'static str>
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        self.root_with_scheme(value, hasher, HashScheme::Unprefixed)
+    }
+
+    pub fn root_with_scheme<H, T>(&self, value: Option<&T>, hasher: &mut H, scheme: HashScheme) -> Result<Hash, &'static str>
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        check_hashes(self.siblings.iter().map(|(_, sibling)| sibling), hasher)?;
+        let defaults = sparse_merkle_tree::default_hashes(scheme, hasher);
+        let mut hash = match value {
+            Some(v) => scheme.try_hash_leaf(v, hasher)?,
+            None => defaults[0].clone(),
+        };
+
+        let mut siblings = self.siblings.iter().peekable();
+        for (height, default) in defaults.iter().enumerate().take(KEY_BITS) {
+            let sibling = match siblings.peek() {
+                Some((h, sibling)) if *h == height => {
+                    siblings.next();
+                    sibling
+                }
+                _ => default,
+            };
+            hash = if sparse_merkle_tree::bit(&self.key, height) {
+                scheme.hash_internal(sibling, &hash, hasher)
+            } else {
+                scheme.hash_internal(&hash, sibling, hasher)
+            };
+        }
+
+        if siblings.next().is_some() {
+            return Err("Proof has unused hashes");
+        }
+        Ok(hash)
+    }
+
+    /// Checks that the proof's key holds `value` in the `Unprefixed` tree
+    /// with the given `root`. With `None`, checks that the key is absent.
+    pub fn verify<H, T>(&self, root: &Hash, value: Option<&T>, hasher: &mut H) -> bool
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        self.verify_with_scheme(root, value, hasher, HashScheme::Unprefixed)
+    }
+
+    pub fn verify_with_scheme<H, T>(&self, root: &Hash, value: Option<&T>, hasher: &mut H, scheme: HashScheme) -> bool
+        where H: MerkleHasher,
+              T: Leaf + ?Sized,
+    {
+        match self.root_with_scheme(value, hasher, scheme) {
+            Ok(hash) => hash == *root,
+            Err(_) => false,
+        }
+    }
+}
+
+/// A proof of the value of one key of a `PatriciaTrie`, or of its absence:
+/// the RLP encoded nodes on the path to the key, from the root down, as in
+/// the `accountProof` and `storageProof` of `eth_getProof`. Verify it with
+/// Keccak-256 to check Ethereum proofs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatriciaProof {
+    key: Vec<u8>,
+    nodes: Vec<Vec<u8>>,
+}
+
+impl PatriciaProof {
+    pub fn new(key: Vec<u8>, nodes: Vec<Vec<u8>>) -> Self {
+        PatriciaProof {
+            key,
+            nodes,
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn nodes(&self) -> &[Vec<u8>] {
+        &self.nodes
+    }
+
+    /// Walks the proof from `root` and returns the value it proves for the
+    /// key, `None` if it proves the key absent.
+    pub fn value<H>(&self, root: &Hash, hasher: &mut H) -> Result<Option<Vec<u8>>, &'static str>
+        where H: MerkleHasher,
+    {
+        let path = patricia_trie::to_nibbles(&self.key);
+        let mut path = &path[..];
+        let mut nodes = self.nodes.iter();
+        let mut node = match nodes.next() {
+            Some(encoded) if patricia_trie::keccak(encoded, hasher) == *root => Rlp::decode(encoded)?,
+            _ => return Err("Proof does not match the root"),
+        };
+
+        let value = loop {
+            let items = match &node {
+                Rlp::Bytes(bytes) if bytes.is_empty() => break None,
+                Rlp::Bytes(_) => return Err("Invalid trie node"),
+                Rlp::List(items) => items,
+            };
+
+            let child = match items.len() {
+                17 => match path.split_first() {
+                    None => break Some(items[16].as_bytes().ok_or("Invalid trie node")?.to_vec()),
+                    Some((&nibble, rest)) => {
+                        path = rest;
+                        &items[nibble as usize]
+                    }
+                },
+                2 => {
+                    let node_path = items[0].as_bytes().ok_or("Invalid trie node")?;
+                    let (node_path, is_leaf) = patricia_trie::from_hex_prefix(node_path)?;
+                    if is_leaf {
+                        break if path == &node_path[..] {
+                            Some(items[1].as_bytes().ok_or("Invalid trie node")?.to_vec())
+                        } else {
+                            None
+                        };
+                    }
+                    if !path.starts_with(&node_path) {
+                        break None;
+                    }
+                    path = &path[node_path.len()..];
+                    &items[1]
+                }
+                _ => return Err("Invalid trie node"),
+            };
+
+            node = match child {
+                Rlp::Bytes(hash) if hash.len() == 32 => match nodes.next() {
+                    Some(encoded) if patricia_trie::keccak(encoded, hasher).as_bytes() == &hash[..] => Rlp::decode(encoded)?,
+                    Some(_) => return Err("Proof node does not match its hash"),
+                    None => return Err("Proof is missing nodes"),
+                },
+                Rlp::Bytes(bytes) if bytes.is_empty() => break None,
+                Rlp::Bytes(_) => return Err("Invalid trie node"),
+                Rlp::List(_) => child.clone(),
+            };
+        };
+
+        if nodes.next().is_some() {
+            return Err("Proof has unused nodes");
+        }
+        Ok(value.filter(|value| !value.is_empty()))
+    }
+
+    /// Checks that the proof's key holds `value` in the trie with the given
+    /// `root`. With `None`, checks that the key is absent.
+    pub fn verify<H>(&self, root: &Hash, value: Option<&[u8]>, hasher: &mut H) -> bool
+        where H: MerkleHasher,
+    {
+        match self.value(root, hasher) {
+            Ok(proven) => proven.as_deref() == value,
+            Err(_) => false,
+        }
+    }
+}
+
+// checks the hashes a proof carries before they reach a hasher that may
+// reject them, see `MerkleHasher::check_input`
+fn check_hashes<'a, H, I>(hashes: I, hasher: &H) -> Result<(), &'static str>
+    where H: MerkleHasher,
+          I: IntoIterator<Item = &'a Hash>,
+{
+    hashes.into_iter().try_for_each(|hash| hasher.check_input(hash.as_bytes()))
+}
+
+#[cfg(all(test, feature = "rust-crypto"))]
+mod tests {
+    use crypto::sha2::Sha256;
+    use crypto::sha3::Sha3;
+    use crate::merkle_tree::{Config, LeafOrder, MerkleTree};
+    use crate::patricia_trie::PatriciaTrie;
+    use crate::sparse_merkle_tree::SparseMerkleTree;
+    use super::*;
+
+    fn drinks() -> Vec<String> {
+        vec![
+            String::from("tea"),
+            String::from("coffee"),
+            String::from("lemonade"),
+            String::from("wine"),
+            String::from("pepsi"),
+            String::from("cola")
+        ]
+    }
+
+    #[test]
+    fn test_proof_path() {
+        let mut leaf_values: Vec<String> = drinks();
+        leaf_values.truncate(4);
+        let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+
+        let proof = mt.proof(0).unwrap();
+        assert_eq!(proof.index(), 0);
+        assert_eq!(proof.path(), &[
+            (Position::Right, Hash::from_hex("37290d74ac4d186e3a8e5785d259d2ec04fac91ae28092e7620ec8bc99e830aa").unwrap()),
+            (Position::Right, Hash::from_hex("deeeedc796079d9cf0762e8f0f261eced1b11ba1d023abef52066abb61d14a08").unwrap()),
+        ]);
+
+        let proof = mt.proof(3).unwrap();
+        assert_eq!(proof.path()[1], (Position::Left, Hash::from_hex("da358a5fff8c2144a68442a0aff90bb0bfd812b8842ea34c067baac3d734fdfc").unwrap()));
+    }
+
+    #[test]
+    fn test_verify() {
+        let leaf_values: Vec<String> = drinks();
+        let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        let root = mt.root_hash().unwrap();
+
+        for (i, value) in drinks().iter().enumerate() {
+            let proof = mt.proof(i).unwrap();
+            assert_eq!(proof.path().len(), 3);
+            assert!(proof.verify(root, value, &mut Sha256::new()));
+            assert!(!proof.verify(root, "water", &mut Sha256::new()));
+        }
+    }
+
+    #[test]
+    fn test_verify_tampered() {
+        let leaf_values: Vec<String> = drinks();
+        let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        let root = mt.root_hash().unwrap();
+
+        let proof = mt.proof(2).unwrap();
+        let mut path = proof.path().to_vec();
+        path.swap(0, 1);
+        let tampered = MerkleProof::new(2, path);
+        assert!(!tampered.verify(root, "lemonade", &mut Sha256::new()));
+
+        let mut path = proof.path().to_vec();
+        path[0].0 = Position::Left;
+        let tampered = MerkleProof::new(2, path);
+        assert!(!tampered.verify(root, "lemonade", &mut Sha256::new()));
+    }
+
+    #[test]
+    fn test_verify_domain_separated() {
+        let leaf_values: Vec<String> = drinks();
+        let config = Config { scheme: HashScheme::DomainSeparated, ..Config::default() };
+        let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+        let root = mt.root_hash().unwrap();
+
+        let proof = mt.proof(4).unwrap();
+        assert!(proof.verify_with_scheme(root, "pepsi", &mut Sha256::new(), HashScheme::DomainSeparated));
+        assert!(!proof.verify(root, "pepsi", &mut Sha256::new()));
+
+        let proof = mt.multi_proof(&[1, 4]).unwrap();
+        let leaves = [String::from("coffee"), String::from("pepsi")];
+        assert!(proof.verify(root, &leaves, 6, &config, &mut Sha256::new()));
+        assert!(!proof.verify(root, &leaves, 6, &Config::default(), &mut Sha256::new()));
+    }
+
+    #[test]
+    fn test_kary_proof() {
+        let leaf_values: Vec<String> = drinks();
+        let config = Config { arity: 4, ..Config::default() };
+        let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+        let root = mt.root_hash().unwrap();
+        assert!(mt.proof(0).is_err());
+
+        for (i, value) in drinks().iter().enumerate() {
+            let proof = mt.kary_proof(i).unwrap();
+            assert_eq!(proof.path().len(), 2);
+            assert_eq!(proof.path()[0].0, i % 4);
+            assert!(proof.path().iter().all(|(_, siblings)| siblings.len() == 3));
+            assert!(proof.verify(root, value, &mut Sha256::new()));
+            assert!(!proof.verify(root, "water", &mut Sha256::new()));
+        }
+
+        // the padding copies of "cola" stand in for the missing leaves
+        let proof = mt.kary_proof(5).unwrap();
+        assert_eq!(proof.path()[0], (1, vec![mt.leaf(4).unwrap().hash().clone(), mt.leaf(5).unwrap().hash().clone(), mt.leaf(5).unwrap().hash().clone()]));
+
+        let mut path = proof.path().to_vec();
+        path[0].0 = 0;
+        assert!(!KaryProof::new(5, path).verify(root, "cola", &mut Sha256::new()));
+        let mut path = proof.path().to_vec();
+        path[1].0 = 4;
+        assert!(KaryProof::new(5, path).root("cola", &mut Sha256::new()).is_err());
+    }
+
+    #[test]
+    fn test_kary_proof_binary() {
+        let leaf_values: Vec<String> = drinks();
+        for &padding in [PaddingStrategy::DuplicateLast, PaddingStrategy::DuplicateOdd, PaddingStrategy::Unbalanced].iter() {
+            let config = Config { padding, ..Config::default() };
+            let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+            for (i, value) in drinks().iter().enumerate() {
+                let proof = mt.kary_proof(i).unwrap();
+                assert_eq!(proof.root(value, &mut Sha256::new()), mt.proof(i).unwrap().root(value, &mut Sha256::new()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_proof() {
+        let leaf_values: Vec<String> = drinks();
+        let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        let root = mt.root_hash().unwrap();
+
+        let proof = mt.multi_proof(&[0, 1]).unwrap();
+        assert_eq!(proof.indices(), &[0, 1]);
+        assert_eq!(proof.hashes(), &[
+            Hash::from_hex("deeeedc796079d9cf0762e8f0f261eced1b11ba1d023abef52066abb61d14a08").unwrap(),
+            Hash::from_hex("ed80cee7334f87f24927b9854fe24db8eb9dcd6cc7857ffb4ea2348f81a21c4c").unwrap(),
+        ]);
+        let leaves = [String::from("tea"), String::from("coffee")];
+        assert!(proof.verify(root, &leaves, 6, &Config::default(), &mut Sha256::new()));
+
+        // siblings shared by both paths are only carried once
+        let proof = mt.multi_proof(&[5, 0, 3]).unwrap();
+        assert_eq!(proof.indices(), &[0, 3, 5]);
+        assert_eq!(proof.hashes().len(), 4);
+        let leaves = [String::from("tea"), String::from("wine"), String::from("cola")];
+        assert!(proof.verify(root, &leaves, 6, &Config::default(), &mut Sha256::new()));
+
+        let leaves = [String::from("tea"), String::from("cola"), String::from("wine")];
+        assert!(!proof.verify(root, &leaves, 6, &Config::default(), &mut Sha256::new()));
+    }
+
+    #[test]
+    fn test_multi_proof_all_leaves() {
+        let mut leaf_values: Vec<String> = drinks();
+        leaf_values.truncate(4);
+        let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+
+        let proof = mt.multi_proof(&[0, 1, 2, 3]).unwrap();
+        assert!(proof.hashes().is_empty());
+
+        leaf_values.truncate(4);
+        assert!(proof.verify(mt.root_hash().unwrap(), &leaf_values, 4, &Config::default(), &mut Sha256::new()));
+    }
+
+    #[test]
+    fn test_multi_proof_malformed() {
+        let leaf_values: Vec<String> = drinks();
+        let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        let root = mt.root_hash().unwrap();
+
+        assert!(mt.multi_proof(&[]).is_err());
+        assert!(mt.multi_proof(&[1, 6]).is_err());
+
+        let config = Config::default();
+        let proof = mt.multi_proof(&[1, 4]).unwrap();
+        let leaves = [String::from("coffee"), String::from("pepsi")];
+        assert!(proof.verify(root, &leaves, 6, &config, &mut Sha256::new()));
+        assert!(proof.root(&leaves[..1], 6, &config, &mut Sha256::new()).is_err());
+
+        let mut hashes = proof.hashes().to_vec();
+        hashes.pop();
+        let truncated = MultiProof::new(proof.leaves(), proof.padding(), proof.indices().to_vec(), hashes);
+        assert!(truncated.root(&leaves, 6, &config, &mut Sha256::new()).is_err());
+
+        let mut hashes = proof.hashes().to_vec();
+        hashes.push(hashes[0].clone());
+        let extended = MultiProof::new(proof.leaves(), proof.padding(), proof.indices().to_vec(), hashes);
+        assert!(extended.root(&leaves, 6, &config, &mut Sha256::new()).is_err());
+
+        let unsorted = MultiProof::new(proof.leaves(), proof.padding(), vec![4, 1], proof.hashes().to_vec());
+        assert!(unsorted.root(&leaves, 6, &config, &mut Sha256::new()).is_err());
+
+        // a proof claiming another size or padding than the verifier's
+        let resized = MultiProof::new(5, proof.padding(), proof.indices().to_vec(), proof.hashes().to_vec());
+        assert!(resized.root(&leaves, 6, &config, &mut Sha256::new()).is_err());
+        assert!(!resized.verify(root, &leaves, 6, &config, &mut Sha256::new()));
+        let repadded = MultiProof::new(6, PaddingStrategy::Zero, proof.indices().to_vec(), proof.hashes().to_vec());
+        assert!(repadded.root(&leaves, 6, &config, &mut Sha256::new()).is_err());
+        assert!(!proof.verify(root, &leaves, 6, &Config { arity: 4, ..config }, &mut Sha256::new()));
+    }
+
+    #[test]
+    fn test_proofs_with_padding() {
+        let strategies = [
+            PaddingStrategy::DuplicateLast,
+            PaddingStrategy::Zero,
+            PaddingStrategy::DuplicateOdd,
+            PaddingStrategy::Unbalanced,
+        ];
+        for &padding in strategies.iter() {
+            for n in 1..=9 {
+                let leaf_values: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+                let config = Config { padding, ..Config::default() };
+                let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+                let root = mt.root_hash().unwrap();
+
+                for (i, value) in leaf_values.iter().enumerate() {
+                    assert!(mt.proof(i).unwrap().verify(root, value, &mut Sha256::new()));
+                }
+
+                let evens: Vec<usize> = (0..n).step_by(2).collect();
+                let last = [n - 1];
+                for indices in [&evens[..], &last[..]].iter() {
+                    let proof = mt.multi_proof(indices).unwrap();
+                    let leaves: Vec<String> = indices.iter().map(|&i| leaf_values[i].clone()).collect();
+                    assert!(proof.verify(root, &leaves, n, &config, &mut Sha256::new()), "{:?} {} {:?}", padding, n, indices);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_proof_unbalanced() {
+        let leaf_values: Vec<String> = drinks();
+        let config = Config { padding: PaddingStrategy::Unbalanced, ..Config::default() };
+        let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+
+        // the last pair is promoted past the second level
+        let proof = mt.proof(5).unwrap();
+        assert_eq!(proof.path().len(), 2);
+        assert_eq!(proof.path()[1].0, Position::Left);
+
+        let proof = mt.multi_proof(&[4]).unwrap();
+        let mut tampered = proof.clone();
+        tampered.padding = PaddingStrategy::DuplicateOdd;
+        let duplicate_odd = Config { padding: PaddingStrategy::DuplicateOdd, ..config };
+        assert!(proof.verify(mt.root_hash().unwrap(), &[String::from("pepsi")], 6, &config, &mut Sha256::new()));
+        assert!(!tampered.verify(mt.root_hash().unwrap(), &[String::from("pepsi")], 6, &config, &mut Sha256::new()));
+        assert!(!tampered.verify(mt.root_hash().unwrap(), &[String::from("pepsi")], 6, &duplicate_odd, &mut Sha256::new()));
+    }
+
+    #[test]
+    fn test_from_audit_path() {
+        for n in 1..=9 {
+            let leaf_values: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+            let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), Config::rfc6962()).unwrap();
+
+            for i in 0..n {
+                let proof = mt.proof(i).unwrap();
+                assert_eq!(MerkleProof::from_audit_path(i, n, proof.audit_path()), Ok(proof));
+            }
+        }
+
+        let mt = MerkleTree::from_leaves_with_config(&drinks(), Sha256::new(), Config::rfc6962()).unwrap();
+        let mut hashes = mt.proof(2).unwrap().audit_path();
+        assert!(MerkleProof::from_audit_path(2, 9, hashes.clone()).is_err());
+        assert!(MerkleProof::from_audit_path(6, 6, hashes.clone()).is_err());
+        hashes.pop();
+        assert!(MerkleProof::from_audit_path(2, 6, hashes.clone()).is_err());
+    }
+
+    #[test]
+    fn test_consistency_proof() {
+        let leaf_values: Vec<String> = (0..9).map(|i| i.to_string()).collect();
+        let mut roots = vec![];
+        for n in 1..=leaf_values.len() {
+            let mt = MerkleTree::from_leaves_with_config(&leaf_values[..n], Sha256::new(), Config::rfc6962()).unwrap();
+            roots.push(mt.root_hash().unwrap().clone());
+        }
+
+        let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), Config::rfc6962()).unwrap();
+        let scheme = HashScheme::DomainSeparated;
+        for old_size in 1..=leaf_values.len() {
+            for new_size in old_size..=leaf_values.len() {
+                let (old_root, new_root) = (&roots[old_size - 1], &roots[new_size - 1]);
+                let proof = mt.consistency_proof(old_size, new_size).unwrap();
+                assert!(proof.verify_with_scheme(old_root, new_root, &mut Sha256::new(), scheme));
+
+                if old_size < new_size {
+                    assert!(!proof.verify_with_scheme(new_root, old_root, &mut Sha256::new(), scheme));
+                    assert!(!proof.verify(old_root, new_root, &mut Sha256::new()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_consistency_proof_tampered() {
+        let leaf_values: Vec<String> = drinks();
+        let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), Config::rfc6962()).unwrap();
+        let old = MerkleTree::from_leaves_with_config(&leaf_values[..3], Sha256::new(), Config::rfc6962()).unwrap();
+        let (old_root, new_root) = (old.root_hash().unwrap(), mt.root_hash().unwrap().clone());
+        let scheme = HashScheme::DomainSeparated;
+
+        let proof = mt.consistency_proof(3, 6).unwrap();
+        assert!(proof.verify_with_scheme(old_root, &new_root, &mut Sha256::new(), scheme));
+
+        let mut hashes = proof.hashes().to_vec();
+        hashes.pop();
+        let truncated = ConsistencyProof::new(3, 6, hashes);
+        assert!(!truncated.verify_with_scheme(old_root, &new_root, &mut Sha256::new(), scheme));
+
+        let resized = ConsistencyProof::new(3, 12, proof.hashes().to_vec());
+        assert!(!resized.verify_with_scheme(old_root, &new_root, &mut Sha256::new(), scheme));
+
+        let mut hashes = proof.hashes().to_vec();
+        hashes.swap(0, 1);
+        let swapped = ConsistencyProof::new(3, 6, hashes);
+        assert!(!swapped.verify_with_scheme(old_root, &new_root, &mut Sha256::new(), scheme));
+
+        assert!(mt.consistency_proof(0, 6).is_err());
+        assert!(mt.consistency_proof(4, 3).is_err());
+        assert!(mt.consistency_proof(3, 7).is_err());
+
+        let padded = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+        assert!(padded.consistency_proof(3, 6).is_err());
+    }
+
+    #[test]
+    fn test_proof_out_of_bounds() {
+        let leaf_values: Vec<String> = drinks();
+        let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+
+        // padded leaves cannot be proven
+        assert!(mt.proof(6).is_err());
+        assert!(mt.proof(8).is_err());
+    }
+
+    #[test]
+    fn test_sparse_merkle_proof() {
+        let mut smt = SparseMerkleTree::new(Sha256::new());
+        let mut keys = vec![];
+        for (i, drink) in drinks().into_iter().enumerate() {
+            let mut key = [0u8; 32];
+            key[0] = i as u8 * 40;
+            key[31] = i as u8;
+            smt.insert(key, drink).unwrap();
+            keys.push(key);
+        }
+        let root = smt.root_hash().clone();
+
+        for (key, drink) in keys.iter().zip(drinks()) {
+            let proof = smt.proof(key);
+            assert!(proof.verify(&root, Some(drink.as_str()), &mut Sha256::new()));
+            assert!(!proof.verify(&root, Some("beer"), &mut Sha256::new()));
+            assert!(!proof.verify::<_, str>(&root, None, &mut Sha256::new()));
+        }
+
+        let absent = [0xffu8; 32];
+        let proof = smt.proof(&absent);
+        assert!(proof.verify::<_, str>(&root, None, &mut Sha256::new()));
+        assert!(!proof.verify(&root, Some("beer"), &mut Sha256::new()));
+    }
+
+    #[test]
+    fn test_sparse_merkle_proof_malformed() {
+        let mut smt = SparseMerkleTree::new(Sha256::new());
+        smt.insert([1u8; 32], String::from("tea")).unwrap();
+        smt.insert([2u8; 32], String::from("coffee")).unwrap();
+        let root = smt.root_hash().clone();
+
+        let proof = smt.proof(&[1u8; 32]);
+        assert_eq!(proof.siblings().len(), 1);
+
+        let mut siblings = proof.siblings().to_vec();
+        siblings.push((KEY_BITS, root.clone()));
+        let padded = SparseMerkleProof::new(*proof.key(), siblings);
+        assert!(padded.root(Some("tea"), &mut Sha256::new()).is_err());
+        assert!(!padded.verify(&root, Some("tea"), &mut Sha256::new()));
+
+        let moved = SparseMerkleProof::new(*proof.key(), vec![(3, proof.siblings()[0].1.clone())]);
+        assert!(!moved.verify(&root, Some("tea"), &mut Sha256::new()));
+    }
+
+    #[test]
+    fn test_non_membership_proof() {
+        let strategies = [
+            PaddingStrategy::DuplicateLast,
+            PaddingStrategy::Zero,
+            PaddingStrategy::DuplicateOdd,
+            PaddingStrategy::Unbalanced,
+        ];
+        for &padding in strategies.iter() {
+            for &order in [LeafOrder::ByValue, LeafOrder::ByHash].iter() {
+                let config = Config { padding, order, ..Config::default() };
+                for n in 1..=9 {
+                    let leaf_values: Vec<String> = (0..n).map(|i| format!("{:02}", i * 2)).collect();
+                    let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+                    let root = mt.root_hash().unwrap().clone();
+
+                    for i in 0..=n * 2 {
+                        let value = format!("{:02}", i);
+                        if i & 1 == 0 && i < n * 2 {
+                            assert!(mt.non_membership_proof(&value).is_err());
+                            continue;
+                        }
+                        let proof = mt.non_membership_proof(&value).unwrap();
+                        assert!(proof.verify(&root, value.as_str(), n, &config, &mut Sha256::new()));
+                        assert!(!proof.verify(&root, "00", n, &config, &mut Sha256::new()));
+                        assert!(!proof.verify(&root, value.as_str(), n + 1, &config, &mut Sha256::new()));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_non_membership_proof_tampered() {
+        let config = Config { order: LeafOrder::ByValue, ..Config::default() };
+        let leaf_values: Vec<String> = ["b", "d", "f", "h", "j"].iter().map(|v| v.to_string()).collect();
+        let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+        let root = mt.root_hash().unwrap().clone();
+        let neighbour = |i: usize| Some((leaf_values[i].as_bytes().to_vec(), mt.proof(i).unwrap()));
+
+        // neighbours that are not adjacent, or around a member
+        let gap = NonMembershipProof::new(5, PaddingStrategy::DuplicateLast, LeafOrder::ByValue, neighbour(0), neighbour(2));
+        assert!(!gap.verify(&root, "c", 5, &config, &mut Sha256::new()));
+        assert!(!gap.verify(&root, "d", 5, &config, &mut Sha256::new()));
+
+        // neighbours in the wrong order
+        let swapped = NonMembershipProof::new(5, PaddingStrategy::DuplicateLast, LeafOrder::ByValue, neighbour(1), neighbour(0));
+        assert!(!swapped.verify(&root, "c", 5, &config, &mut Sha256::new()));
+
+        // a leaf posing as the last one, whether or not the verifier is
+        // told the smaller size
+        let short = NonMembershipProof::new(4, PaddingStrategy::DuplicateLast, LeafOrder::ByValue, neighbour(3), None);
+        assert!(!short.verify(&root, "i", 5, &config, &mut Sha256::new()));
+        assert!(!short.verify(&root, "i", 4, &config, &mut Sha256::new()));
+        let unsorted = NonMembershipProof::new(5, PaddingStrategy::DuplicateLast, LeafOrder::Insertion, neighbour(0), neighbour(1));
+        assert!(!unsorted.verify(&root, "c", 5, &config, &mut Sha256::new()));
+        let padded = NonMembershipProof::new(5, PaddingStrategy::Zero, LeafOrder::ByValue, neighbour(0), neighbour(1));
+        assert!(!padded.verify(&root, "c", 5, &config, &mut Sha256::new()));
+
+        let last = mt.non_membership_proof(&String::from("k")).unwrap();
+        assert!(last.verify(&root, "k", 5, &config, &mut Sha256::new()));
+        assert!(last.right().is_none());
+    }
+
+    #[test]
+    fn test_non_membership_proof_relabelled() {
+        // adjacent leaves of a tree sorted one way that happen to bracket a
+        // member when compared the other way
+        for &(order, relabelled) in [(LeafOrder::ByHash, LeafOrder::ByValue), (LeafOrder::ByValue, LeafOrder::ByHash)].iter() {
+            let config = Config { order, ..Config::default() };
+            let leaf_values: Vec<String> = (0..16).map(|i| format!("{:02}", i)).collect();
+            let mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+            let root = mt.root_hash().unwrap().clone();
+            let sorted: Vec<String> = mt.leaves().map(|leaf| leaf.value().unwrap().clone()).collect();
+            let key = |value: &str| {
+                let hash = HashScheme::Unprefixed.hash_leaf(value, &mut Sha256::new());
+                relabelled.key(value, &hash).into_owned()
+            };
+
+            let mut forged = 0;
+            for member in leaf_values.iter() {
+                for i in 0..sorted.len() - 1 {
+                    if !(key(&sorted[i]) < key(member) && key(member) < key(&sorted[i + 1])) {
+                        continue;
+                    }
+                    let proof = NonMembershipProof::new(
+                        16,
+                        PaddingStrategy::DuplicateLast,
+                        relabelled,
+                        Some((sorted[i].as_bytes().to_vec(), mt.proof(i).unwrap())),
+                        Some((sorted[i + 1].as_bytes().to_vec(), mt.proof(i + 1).unwrap())),
+                    );
+                    assert!(!proof.verify(&root, member.as_str(), 16, &config, &mut Sha256::new()));
+                    // only a verifier mistaken about the order accepts it
+                    let mistaken = Config { order: relabelled, ..config };
+                    assert!(proof.verify(&root, member.as_str(), 16, &mistaken, &mut Sha256::new()));
+                    forged += 1;
+                }
+            }
+            assert!(forged > 0);
+        }
+    }
+
+    #[test]
+    fn test_patricia_proof() {
+        let mut trie = PatriciaTrie::new(Sha3::keccak256());
+        for (key, value) in [("do", "verb"), ("horse", "stallion"), ("doge", "coin"), ("dog", "puppy")].iter() {
+            trie.insert(key.as_bytes(), value.as_bytes());
+        }
+        let root = trie.root_hash();
+
+        for (key, value) in [("do", "verb"), ("horse", "stallion"), ("doge", "coin"), ("dog", "puppy")].iter() {
+            let proof = trie.proof(key.as_bytes());
+            assert!(proof.verify(&root, Some(value.as_bytes()), &mut Sha3::keccak256()));
+            assert!(!proof.verify(&root, Some(b"cat"), &mut Sha3::keccak256()));
+        }
+        for key in ["d", "dogs", "horses", "cat", ""].iter() {
+            let proof = trie.proof(key.as_bytes());
+            assert_eq!(proof.value(&root, &mut Sha3::keccak256()), Ok(None));
+        }
+
+        let mut empty = PatriciaTrie::new(Sha3::keccak256());
+        let proof = empty.proof(b"dog");
+        assert!(proof.verify(&empty.root_hash(), None, &mut Sha3::keccak256()));
+    }
+
+    #[test]
+    fn test_patricia_proof_malformed() {
+        let mut trie = PatriciaTrie::new(Sha3::keccak256());
+        for i in 0..32u8 {
+            trie.insert(&[i, i], &[i; 40]);
+        }
+        let root = trie.root_hash();
+        let proof = trie.proof(&[7, 7]);
+        assert_eq!(proof.nodes().len(), 3);
+
+        let wrong_root = PatriciaProof::new(vec![7, 7], proof.nodes()[1..].to_vec());
+        assert!(wrong_root.value(&root, &mut Sha3::keccak256()).is_err());
+
+        let mut nodes = proof.nodes().to_vec();
+        nodes.push(nodes[0].clone());
+        let padded = PatriciaProof::new(vec![7, 7], nodes);
+        assert!(padded.value(&root, &mut Sha3::keccak256()).is_err());
+
+        let moved = PatriciaProof::new(vec![8, 8], proof.nodes().to_vec());
+        assert!(moved.value(&root, &mut Sha3::keccak256()).is_err());
+    }
+}
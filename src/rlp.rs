@@ -0,0 +1,160 @@
+/// An item of Ethereum's Recursive Length Prefix encoding: a byte string or
+/// a list of items.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rlp {
+    Bytes(Vec<u8>),
+    List(Vec<Rlp>),
+}
+
+impl Rlp {
+    /// The byte string for an unsigned integer: big-endian, without
+    /// leading zeros, so that zero is the empty string.
+    pub fn from_uint(value: u64) -> Self {
+        let bytes = value.to_be_bytes();
+        let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+        Rlp::Bytes(bytes[start..].to_vec())
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Rlp::Bytes(bytes) => Some(bytes),
+            Rlp::List(_) => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Rlp]> {
+        match self {
+            Rlp::Bytes(_) => None,
+            Rlp::List(items) => Some(items),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Rlp::Bytes(bytes) => encode_bytes(bytes),
+            Rlp::List(items) => {
+                let items: Vec<Vec<u8>> = items.iter().map(Rlp::encode).collect();
+                encode_list(&items)
+            }
+        }
+    }
+
+    /// Decodes a single item spanning all of `data`.
+    pub fn decode(data: &[u8]) -> Result<Self, &'static str> {
+        let (item, rest) = decode_item(data)?;
+        if !rest.is_empty() {
+            return Err("Trailing bytes after RLP item");
+        }
+        Ok(item)
+    }
+}
+
+/// Encodes a byte string.
+pub(crate) fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut out = length_prefix(bytes.len(), 0x80);
+    out.extend_from_slice(bytes);
+    out
+}
+
+/// Encodes a list of already encoded items.
+pub(crate) fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let len = items.iter().map(Vec::len).sum();
+    let mut out = length_prefix(len, 0xc0);
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+fn length_prefix(len: usize, offset: u8) -> Vec<u8> {
+    if len < 56 {
+        return vec![offset + len as u8];
+    }
+    let bytes = len.to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    let mut out = vec![offset + 55 + (bytes.len() - start) as u8];
+    out.extend_from_slice(&bytes[start..]);
+    out
+}
+
+// decodes the item at the start of `data`, returning it and what follows
+fn decode_item(data: &[u8]) -> Result<(Rlp, &[u8]), &'static str> {
+    let first = *data.first().ok_or("Unexpected end of RLP data")?;
+    if first < 0x80 {
+        return Ok((Rlp::Bytes(vec![first]), &data[1..]));
+    }
+
+    let (offset, is_list) = if first < 0xc0 { (0x80, false) } else { (0xc0, true) };
+    let (header, len) = if first - offset < 56 {
+        (1, (first - offset) as usize)
+    } else {
+        let len_of_len = (first - offset - 55) as usize;
+        let len_bytes = data.get(1..1 + len_of_len).ok_or("Unexpected end of RLP data")?;
+        if len_bytes[0] == 0 || len_of_len > std::mem::size_of::<usize>() {
+            return Err("Non-canonical RLP length");
+        }
+        let len = len_bytes.iter().fold(0usize, |len, &b| len << 8 | b as usize);
+        if len < 56 {
+            return Err("Non-canonical RLP length");
+        }
+        (1 + len_of_len, len)
+    };
+
+    let end = header.checked_add(len).ok_or("Unexpected end of RLP data")?;
+    let payload = data.get(header..end).ok_or("Unexpected end of RLP data")?;
+    let rest = &data[end..];
+    if !is_list {
+        if len == 1 && payload[0] < 0x80 {
+            return Err("Non-canonical RLP byte");
+        }
+        return Ok((Rlp::Bytes(payload.to_vec()), rest));
+    }
+
+    let mut items = vec![];
+    let mut payload = payload;
+    while !payload.is_empty() {
+        let (item, remaining) = decode_item(payload)?;
+        items.push(item);
+        payload = remaining;
+    }
+    Ok((Rlp::List(items), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(Rlp::Bytes(b"dog".to_vec()).encode(), b"\x83dog");
+        assert_eq!(Rlp::Bytes(vec![]).encode(), [0x80]);
+        assert_eq!(Rlp::Bytes(vec![0x0f]).encode(), [0x0f]);
+        assert_eq!(Rlp::from_uint(0).encode(), [0x80]);
+        assert_eq!(Rlp::from_uint(1024).encode(), [0x82, 0x04, 0x00]);
+        assert_eq!(Rlp::List(vec![]).encode(), [0xc0]);
+
+        let list = Rlp::List(vec![Rlp::Bytes(b"cat".to_vec()), Rlp::Bytes(b"dog".to_vec())]);
+        assert_eq!(list.encode(), b"\xc8\x83cat\x83dog");
+
+        let long = Rlp::Bytes(b"Lorem ipsum dolor sit amet, consectetur adipisicing elit".to_vec());
+        assert_eq!(&long.encode()[..2], [0xb8, 0x38]);
+    }
+
+    #[test]
+    fn test_decode() {
+        let nested = Rlp::List(vec![
+            Rlp::List(vec![]),
+            Rlp::List(vec![Rlp::List(vec![])]),
+            Rlp::Bytes(vec![0xff; 60]),
+        ]);
+        assert_eq!(Rlp::decode(&nested.encode()), Ok(nested));
+
+        assert!(Rlp::decode(&[0x83, b'd', b'o']).is_err());
+        assert!(Rlp::decode(&[0x81, 0x05]).is_err());
+        assert!(Rlp::decode(&[0xb8, 0x05, 1, 2, 3, 4, 5]).is_err());
+        assert!(Rlp::decode(&[0x80, 0x80]).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::hash::{Hash, HashScheme};
+use crate::hasher::MerkleHasher;
+use crate::leaf::Leaf;
+use crate::proof::SparseMerkleProof;
+
+/// Number of bits in a key, and so of levels below the root.
+pub const KEY_BITS: usize = 256;
+
+/// A merkle tree over the whole 256-bit key space, where every key has a
+/// leaf: the hash of its value, or an all-zero hash while it is absent.
+/// Subtrees holding no value hash to precomputed defaults, so only the
+/// nodes above present keys are stored.
+pub struct SparseMerkleTree<H, V>
+    where H: MerkleHasher,
+          V: Leaf + Clone,
+{
+    hasher: H,
+    scheme: HashScheme,
+    values: BTreeMap<[u8; 32], V>,
+    // non-default nodes, keyed by height above the leaves and the key
+    // prefix leading to them
+    nodes: HashMap<(usize, [u8; 32]), Hash>,
+    // hash of an empty subtree at each height, leaves through root
+    defaults: Vec<Hash>,
+}
+
+impl<H, V> SparseMerkleTree<H, V>
+    where H: MerkleHasher,
+          V: Leaf + Clone,
+{
+    pub fn new(hasher: H) -> Self {
+        Self::with_scheme(hasher, HashScheme::default())
+    }
+
+    pub fn with_scheme(mut hasher: H, scheme: HashScheme) -> Self {
+        let defaults = default_hashes(scheme, &mut hasher);
+        SparseMerkleTree {
+            hasher,
+            scheme,
+            values: BTreeMap::new(),
+            nodes: HashMap::new(),
+            defaults,
+        }
+    }
+
+    pub fn scheme(&self) -> HashScheme {
+        self.scheme
+    }
+
+    pub fn root_hash(&self) -> &Hash {
+        self.node(KEY_BITS, &[0u8; 32])
+    }
+
+    /// Number of keys holding a value.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get(&self, key: &[u8; 32]) -> Option<&V> {
+        self.values.get(key)
+    }
+
+    /// Sets the value of `key`, returning the value it replaces. Fails,
+    /// leaving the tree as it was, when the hasher rejects the value, see
+    /// `MerkleHasher::check_input`.
+    pub fn insert(&mut self, key: [u8; 32], value: V) -> Result<Option<V>, &'static str> {
+        let leaf = self.scheme.try_hash_leaf(&value, &mut self.hasher)?;
+        self.update(&key, leaf);
+        Ok(self.values.insert(key, value))
+    }
+
+    /// Clears the value of `key`, returning it.
+    pub fn remove(&mut self, key: &[u8; 32]) -> Option<V> {
+        let removed = self.values.remove(key);
+        if removed.is_some() {
+            let leaf = self.defaults[0].clone();
+            self.update(key, leaf);
+        }
+        removed
+    }
+
+    /// A proof of the value of `key`: of membership if it holds one, of
+    /// non-membership otherwise.
+    pub fn proof(&self, key: &[u8; 32]) -> SparseMerkleProof {
+        let mut prefix = *key;
+        let mut siblings = vec![];
+        for height in 0..KEY_BITS {
+            if let Some(hash) = self.nodes.get(&(height, sibling_prefix(&prefix, height))) {
+                siblings.push((height, hash.clone()));
+            }
+            clear_bit(&mut prefix, height);
+        }
+        SparseMerkleProof::new(*key, siblings)
+    }
+
+    // sets the leaf of `key` and rehashes its path up to the root
+    fn update(&mut self, key: &[u8; 32], leaf: Hash) {
+        let mut hash = leaf;
+        let mut prefix = *key;
+        for height in 0..KEY_BITS {
+            let sibling = self.node(height, &sibling_prefix(&prefix, height)).clone();
+            let parent = if bit(key, height) {
+                self.scheme.hash_internal(&sibling, &hash, &mut self.hasher)
+            } else {
+                self.scheme.hash_internal(&hash, &sibling, &mut self.hasher)
+            };
+            self.set_node(height, prefix, hash);
+            clear_bit(&mut prefix, height);
+            hash = parent;
+        }
+        self.set_node(KEY_BITS, [0u8; 32], hash);
+    }
+
+    fn node(&self, height: usize, prefix: &[u8; 32]) -> &Hash {
+        self.nodes.get(&(height, *prefix)).unwrap_or(&self.defaults[height])
+    }
+
+    fn set_node(&mut self, height: usize, prefix: [u8; 32], hash: Hash) {
+        if hash == self.defaults[height] {
+            self.nodes.remove(&(height, prefix));
+        } else {
+            self.nodes.insert((height, prefix), hash);
+        }
+    }
+}
+
+/// Hashes of an empty subtree at each height, from a single empty leaf
+/// (all zeros) up to the root of an empty tree.
+pub(crate) fn default_hashes<H>(scheme: HashScheme, hasher: &mut H) -> Vec<Hash>
+    where H: MerkleHasher,
+{
+    let mut defaults = Vec::with_capacity(KEY_BITS + 1);
+    defaults.push(Hash::zero(hasher.output_len()));
+    for height in 1..=KEY_BITS {
+        let below = &defaults[height - 1];
+        let hash = scheme.hash_internal(below, below, hasher);
+        defaults.push(hash);
+    }
+    defaults
+}
+
+/// Whether the node at `height` on the path to `key` is a right child. The
+/// most significant bit of the key picks the side just below the root.
+pub(crate) fn bit(key: &[u8; 32], height: usize) -> bool {
+    key[31 - height / 8] >> (height % 8) & 1 == 1
+}
+
+// A node is named by the key of any leaf below it with the bits below its
+// height cleared. Walking a path up from a key clears one bit per level,
+// turning the prefix of a node into the prefix of its parent.
+fn clear_bit(prefix: &mut [u8; 32], height: usize) {
+    prefix[31 - height / 8] &= !(1 << (height % 8));
+}
+
+// the prefix of the sibling of the node at `height` named by `prefix`
+fn sibling_prefix(prefix: &[u8; 32], height: usize) -> [u8; 32] {
+    let mut sibling = *prefix;
+    sibling[31 - height / 8] ^= 1 << (height % 8);
+    sibling
+}
+
+#[cfg(all(test, feature = "rust-crypto"))]
+mod tests {
+    use crypto::sha2::Sha256;
+    use super::*;
+
+    fn key(last: u8) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key[31] = last;
+        key
+    }
+
+    #[test]
+    fn test_empty() {
+        let smt: SparseMerkleTree<Sha256, String> = SparseMerkleTree::new(Sha256::new());
+        assert!(smt.is_empty());
+        assert_eq!(smt.root_hash(), &smt.defaults[KEY_BITS]);
+        assert_eq!(smt.root_hash().to_hex(), "b178c245c947ea7e21ecede07728941a6ab1b706143c06873baff8ebd6de6308");
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut smt = SparseMerkleTree::new(Sha256::new());
+        let empty_root = smt.root_hash().clone();
+
+        assert_eq!(smt.insert(key(1), String::from("tea")), Ok(None));
+        assert_eq!(smt.insert(key(2), String::from("coffee")), Ok(None));
+        assert_eq!(smt.root_hash().to_hex(), "88239a12f8a19a2fb5e623e0fd12adf179d898658452d12d6293e0dac30ad068");
+        assert_eq!(smt.insert(key(2), String::from("wine")), Ok(Some(String::from("coffee"))));
+        assert_eq!(smt.get(&key(2)), Some(&String::from("wine")));
+        assert_eq!(smt.get(&key(3)), None);
+        assert_eq!(smt.len(), 2);
+
+        assert_eq!(smt.remove(&key(3)), None);
+        assert_eq!(smt.remove(&key(1)), Some(String::from("tea")));
+        assert_eq!(smt.remove(&key(2)), Some(String::from("wine")));
+        assert_eq!(smt.root_hash(), &empty_root);
+        assert!(smt.nodes.is_empty());
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut forward = SparseMerkleTree::new(Sha256::new());
+        let mut backward = SparseMerkleTree::new(Sha256::new());
+        for i in 0..8 {
+            forward.insert(key(i * 31), i.to_string()).unwrap();
+            backward.insert(key((7 - i) * 31), (7 - i).to_string()).unwrap();
+        }
+        assert_eq!(forward.root_hash(), backward.root_hash());
+    }
+
+    #[test]
+    fn test_bit_and_prefix() {
+        let mut k = [0u8; 32];
+        k[0] = 0x80;
+        k[31] = 0x05;
+        assert!(bit(&k, 0));
+        assert!(!bit(&k, 1));
+        assert!(bit(&k, 2));
+        assert!(bit(&k, 255));
+        clear_bit(&mut k, 0);
+        assert_eq!(k[31], 0x04);
+        assert_eq!(sibling_prefix(&k, 1)[31], 0x06);
+        for height in 1..KEY_BITS {
+            clear_bit(&mut k, height);
+        }
+        assert_eq!(k, [0u8; 32]);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::hash::Hash;
+use crate::hasher::MerkleHasher;
+use crate::leaf::Leaf;
+use crate::merkle_tree::Config;
+use crate::padding;
+
+/// Computes the root of a `MerkleTree` without building it, from leaves
+/// pushed one at a time. Only the frontier is kept: at each level, the
+/// nodes still waiting for the rest of their siblings, fewer than the
+/// arity. Memory grows with the log of the number of leaves.
+///
+/// The root is the one `MerkleTree::from_leaves_with_config` gives for the
+/// same leaves and config. Sorted orders need every leaf up front, and are
+/// rejected.
+pub struct StreamingRoot<H>
+    where H: MerkleHasher,
+{
+    hasher: H,
+    config: Config,
+    // nodes of each level whose parent isn't complete yet
+    frontier: Vec<Vec<Hash>>,
+    len: usize,
+    last_leaf: Option<Hash>,
+}
+
+impl<H> StreamingRoot<H>
+    where H: MerkleHasher,
+{
+    pub fn new(hasher: H, config: Config) -> Result<Self, &'static str> {
+        config.check()?;
+        if config.order.is_sorted() {
+            return Err("Streaming roots require leaves in insertion order");
+        }
+
+        Ok(StreamingRoot {
+            hasher,
+            config,
+            frontier: vec![],
+            len: 0,
+            last_leaf: None,
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Number of leaves pushed so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push<T>(&mut self, value: &T) -> Result<(), &'static str>
+        where T: Leaf + ?Sized,
+    {
+        let hash = self.config.scheme.try_hash_leaf(value, &mut self.hasher)?;
+        self.push_hash(hash)
+    }
+
+    /// Pushes a leaf hashed beforehand with `config.scheme.hash_leaf`.
+    pub fn push_hash(&mut self, hash: Hash) -> Result<(), &'static str> {
+        self.hasher.check_input(hash.as_bytes())?;
+        self.len += 1;
+        self.last_leaf = Some(hash.clone());
+
+        // hash every group this leaf completes, carrying the parent up
+        let mut node = hash;
+        let mut level = 0;
+        loop {
+            if level == self.frontier.len() {
+                self.frontier.push(Vec::with_capacity(self.config.arity));
+            }
+            let pending = &mut self.frontier[level];
+            pending.push(node);
+            if pending.len() < self.config.arity {
+                break;
+            }
+
+            let children: Vec<&Hash> = pending.iter().collect();
+            node = self.config.scheme.hash_children(&children, &mut self.hasher);
+            pending.clear();
+            level += 1;
+        }
+        Ok(())
+    }
+
+    /// Root of the tree over the leaves pushed so far, which the padding
+    /// strategy completes as `MerkleTree` does. More leaves can be pushed
+    /// afterwards.
+    pub fn root(&mut self) -> Result<Hash, &'static str> {
+        let last_leaf = match &self.last_leaf {
+            Some(hash) => hash.clone(),
+            None => return Err("Leaves cannot be empty"),
+        };
+        let arity = self.config.arity;
+        let depth = padding::depth(self.len, arity);
+        let padding = self.config.padding.padding_hashes(&last_leaf, depth, arity, self.config.scheme, &mut self.hasher);
+
+        // the last node of each level, below it either a complete subtree
+        // already in the frontier or the node carried up from the level
+        // below
+        let mut carry: Option<Hash> = None;
+        for level in 0..depth - 1 {
+            let mut group: Vec<&Hash> = self.frontier[level].iter().collect();
+            group.extend(carry.as_ref());
+            if group.is_empty() {
+                continue;
+            }
+
+            let missing = if group.len() < arity {
+                self.config.padding.missing_sibling(group[group.len() - 1], padding.get(level))
+            } else {
+                None
+            };
+            if let Some(sibling) = &missing {
+                group.resize(arity, sibling);
+            }
+            let parent = match group.len() {
+                1 => group[0].clone(),
+                _ => self.config.scheme.hash_children(&group, &mut self.hasher),
+            };
+            carry = Some(parent);
+        }
+
+        match carry {
+            Some(root) => Ok(root),
+            // a complete tree, whose root is the only node of its top level
+            None => Ok(self.frontier[depth - 1][0].clone()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "rust-crypto"))]
+mod tests {
+    use crypto::sha2::Sha256;
+    use super::*;
+    use crate::hash::HashScheme;
+    use crate::merkle_tree::{LeafOrder, MerkleTree};
+    use crate::padding::PaddingStrategy;
+
+    #[test]
+    fn test_matches_tree() {
+        let paddings = [
+            PaddingStrategy::DuplicateLast,
+            PaddingStrategy::Zero,
+            PaddingStrategy::DuplicateOdd,
+            PaddingStrategy::Unbalanced,
+        ];
+        let leaf_values: Vec<String> = (0..70).map(|i| i.to_string()).collect();
+        for &padding in paddings.iter() {
+            for &arity in [2, 3, 4, 16].iter() {
+                if arity > 2 && !padding.pads_to_power_of_two() {
+                    continue;
+                }
+                for &scheme in [HashScheme::Unprefixed, HashScheme::DomainSeparated].iter() {
+                    let config = Config { padding, arity, scheme, ..Config::default() };
+                    let mut streaming = StreamingRoot::new(Sha256::new(), config).unwrap();
+                    for n in 1..=leaf_values.len() {
+                        streaming.push(&leaf_values[n - 1]).unwrap();
+                        let expected = MerkleTree::from_leaves_with_config(&leaf_values[..n], Sha256::new(), config).unwrap();
+                        assert_eq!(streaming.root().as_ref().ok(), expected.root_hash().ok(), "{:?}, {} leaves", config, n);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_frontier() {
+        let mut streaming = StreamingRoot::new(Sha256::new(), Config::default()).unwrap();
+        assert!(streaming.root().is_err());
+        for i in 0..1000u32 {
+            streaming.push(&i).unwrap();
+        }
+        assert_eq!(streaming.len(), 1000);
+        // one level per bit of the count, each holding at most one node
+        assert_eq!(streaming.frontier.len(), 10);
+        assert!(streaming.frontier.iter().all(|pending| pending.len() <= 1));
+
+        let config = Config { order: LeafOrder::ByHash, ..Config::default() };
+        assert!(StreamingRoot::new(Sha256::new(), config).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "rust-crypto")]
+
+extern crate crypto;
+extern crate merkle_tree;
+extern crate serde_json;
+
+use merkle_tree::{bitcoin_merkle_root, Config, DoubleSha256, Hash, MerkleBlock, MerkleTree, PartialMerkleTree};
+use serde_json::Value;
+
+const FIXTURE: &str = include_str!("fixtures/bitcoin_blocks.json");
+
+fn blocks() -> Vec<Value> {
+    let fixture: Value = serde_json::from_str(FIXTURE).unwrap();
+    fixture["blocks"].as_array().unwrap().clone()
+}
+
+// block explorers display hashes byte-reversed
+fn displayed(value: &Value) -> Hash {
+    Hash::from_hex(value.as_str().unwrap()).unwrap().reversed()
+}
+
+fn txids(block: &Value) -> Vec<Hash> {
+    block["tx"].as_array().unwrap().iter().map(displayed).collect()
+}
+
+#[test]
+fn test_merkle_root() {
+    for block in blocks() {
+        let root = bitcoin_merkle_root(&txids(&block)).unwrap();
+        assert_eq!(root, displayed(&block["merkleroot"]), "block {}", block["height"]);
+    }
+}
+
+#[test]
+fn test_merkle_tree_config() {
+    let transactions: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i; 100]).collect();
+    let mt = MerkleTree::from_leaves_with_config(&transactions, DoubleSha256::new(), Config::bitcoin()).unwrap();
+
+    let txids: Vec<Hash> = mt.leaves().map(|leaf| leaf.hash().clone()).collect();
+    assert_eq!(mt.root_hash().unwrap(), &bitcoin_merkle_root(&txids).unwrap());
+}
+
+#[test]
+fn test_merkleblock() {
+    let block = &blocks()[2];
+    let encoded = Hash::from_hex(block["merkleblock"]["hex"].as_str().unwrap()).unwrap();
+    let merkle_block = MerkleBlock::decode(encoded.as_bytes()).unwrap();
+
+    assert_eq!(merkle_block.block_hash(), displayed(&block["hash"]));
+    assert_eq!(merkle_block.merkle_root(), displayed(&block["merkleroot"]));
+    assert_eq!(merkle_block.encode(), encoded.as_bytes());
+
+    let txids = txids(block);
+    assert_eq!(merkle_block.matches().unwrap(), vec![(2, txids[2].clone())]);
+
+    let tree = PartialMerkleTree::from_txids(&txids, &[false, false, true, false]).unwrap();
+    assert_eq!(merkle_block.tree().encode(), tree.encode());
+}
+
+#[test]
+fn test_merkleblock_wrong_header() {
+    let block = &blocks()[2];
+    let header = Hash::from_hex(block["header"].as_str().unwrap()).unwrap();
+    let mut header_bytes = [0u8; 80];
+    header_bytes.copy_from_slice(header.as_bytes());
+
+    let other_txids = txids(&blocks()[1]);
+    let tree = PartialMerkleTree::from_txids(&other_txids, &[true, false]).unwrap();
+    let merkle_block = MerkleBlock::new(header_bytes, tree);
+    assert!(merkle_block.matches().is_err());
+
+    let truncated = merkle_block.encode();
+    assert!(MerkleBlock::decode(&truncated[..truncated.len() - 1]).is_err());
+}
@@ -0,0 +1,91 @@
+#[cfg(feature = "rust-crypto")]
+extern crate crypto;
+extern crate merkle_tree;
+extern crate sha2;
+
+use merkle_tree::{MerkleHasher, MerkleTree};
+use sha2::{Digest, Sha256};
+
+// SHA-256 truncated to 20 bytes, as a hasher owned by a downstream crate
+struct Truncated;
+
+impl MerkleHasher for Truncated {
+    type Output = [u8; 20];
+
+    fn output_len(&self) -> usize {
+        20
+    }
+
+    fn hash(&mut self, parts: &[&[u8]]) -> [u8; 20] {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        let mut out = [0u8; 20];
+        out.copy_from_slice(&hasher.finalize()[..20]);
+        out
+    }
+}
+
+#[test]
+fn test_custom_hasher() {
+    let leaf_values = ["tea", "coffee", "lemonade"];
+    let mt = MerkleTree::from_leaves(&leaf_values.iter().map(|v| v.to_string()).collect::<Vec<_>>(), Truncated).unwrap();
+
+    let root = mt.root_hash().unwrap();
+    assert_eq!(root.len(), 20);
+    for (i, value) in leaf_values.iter().enumerate() {
+        assert!(mt.proof(i).unwrap().verify(root, *value, &mut Truncated));
+    }
+}
+
+#[cfg(all(feature = "digest", feature = "rust-crypto"))]
+#[test]
+fn test_digest_hasher() {
+    use merkle_tree::{Config, DigestHasher};
+
+    let leaf_values: Vec<String> = ["tea", "coffee", "lemonade", "wine", "pepsi"].iter().map(|v| v.to_string()).collect();
+    let config = Config::rfc6962();
+    let mt = MerkleTree::from_leaves_with_config(&leaf_values, DigestHasher::<Sha256>::new(), config).unwrap();
+    let legacy = MerkleTree::from_leaves_with_config(&leaf_values, crypto::sha2::Sha256::new(), config).unwrap();
+    assert_eq!(mt.root_hash(), legacy.root_hash());
+}
+
+// Regression roots over the six drinks with the default config, as computed
+// by this crate; they catch a backend changing its output, not a wrong one.
+// The known-answer tests for the hash functions themselves are the "abc"
+// vectors in src/hasher.rs.
+#[cfg(any(feature = "sha2", feature = "sha3", feature = "blake3"))]
+fn check_backend<H>(mut hasher: H, root: &str)
+    where H: MerkleHasher + Clone,
+{
+    let leaf_values: Vec<String> = ["tea", "coffee", "lemonade", "wine", "pepsi", "cola"].iter().map(|v| v.to_string()).collect();
+    let mt = MerkleTree::from_leaves(&leaf_values, hasher.clone()).unwrap();
+    assert_eq!(mt.root_hash().unwrap().to_hex(), root);
+
+    for (i, value) in leaf_values.iter().enumerate() {
+        let proof = mt.proof(i).unwrap();
+        assert!(proof.verify(mt.root_hash().unwrap(), value, &mut hasher));
+        assert!(!proof.verify(mt.root_hash().unwrap(), "water", &mut hasher));
+    }
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn test_sha2_backends() {
+    check_backend(merkle_tree::Sha256::new(), "a319d5ff88ec23b8694c166b086c5061f90794791b541f2b963138ebaaaa0ac8");
+    check_backend(merkle_tree::Sha512_256::new(), "77f1e0423b7a76757b60fa3eef774d15b9f837f94c44f9573b81ad3ffcc36b29");
+}
+
+#[cfg(feature = "sha3")]
+#[test]
+fn test_sha3_backends() {
+    check_backend(merkle_tree::Sha3_256::new(), "6cdbcb3daae8841f5fda9ea8c48b7c999f0a1ee81e4c733397611ea282f3be49");
+    check_backend(merkle_tree::Keccak256::new(), "8e1d14a8dd265b54afc3c65990dd86743df18417c30607d7578059861a4f4e8b");
+}
+
+#[cfg(feature = "blake3")]
+#[test]
+fn test_blake3_backend() {
+    check_backend(merkle_tree::Blake3::new(), "7b4f83f7e13ac9a3e97e04bad8417cd3123dc04ed8627a8dcf38a0b58a049350");
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "rust-crypto")]
+
+extern crate crypto;
+extern crate merkle_tree;
+extern crate merkle_tree_derive;
+
+use crypto::sha2::Sha256;
+use merkle_tree::{Config, Leaf, LeafOrder, LittleEndian, MerkleTree};
+
+#[derive(Clone, Debug, PartialEq, merkle_tree_derive::Leaf)]
+struct Transfer {
+    from: String,
+    to: String,
+    amount: u64,
+}
+
+#[derive(Clone, merkle_tree_derive::Leaf)]
+struct Pair(LittleEndian<u32>, [u8; 4]);
+
+#[derive(Clone, merkle_tree_derive::Leaf)]
+struct Tagged<T> {
+    tag: u8,
+    inner: T,
+}
+
+fn transfer(from: &str, to: &str, amount: u64) -> Transfer {
+    Transfer { from: from.to_string(), to: to.to_string(), amount }
+}
+
+fn transfers() -> Vec<Transfer> {
+    vec![transfer("alice", "bob", 10), transfer("bob", "carol", 5), transfer("carol", "alice", 1)]
+}
+
+#[test]
+fn test_derive() {
+    let bytes = transfer("alice", "bob", 10).leaf_bytes().into_owned();
+    assert_eq!(&bytes[..8], [0, 0, 0, 0, 0, 0, 0, 5]);
+    assert_eq!(&bytes[8..13], b"alice");
+    assert_eq!(&bytes[bytes.len() - 8..], [0, 0, 0, 0, 0, 0, 0, 10]);
+
+    assert_eq!(&*Pair(LittleEndian(1), [2; 4]).leaf_bytes(), [1, 0, 0, 0, 2, 2, 2, 2]);
+    assert_eq!(&*Tagged { tag: 7, inner: 1u16 }.leaf_bytes(), [7, 0, 1]);
+    assert_ne!(transfer("al", "icebob", 10).leaf_bytes(), transfer("alice", "bob", 10).leaf_bytes());
+}
+
+#[test]
+fn test_struct_leaves() {
+    let leaf_values = transfers();
+    let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+    assert_eq!(mt.leaf(0).unwrap().hash().to_hex(), "ab6402c653e22382ceee8441e964b70aa04403f88b8a63056f3c9f5f2abf6067");
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "9bd256b402b884e4a376ef668e6ddbb21e1adce7b638fdf2fe384268d5824ea0");
+    assert_eq!(mt.leaf(1).unwrap().value(), Some(&leaf_values[1]));
+
+    let root = mt.root_hash().unwrap();
+    let proof = mt.proof(2).unwrap();
+    assert!(proof.verify(root, &leaf_values[2], &mut Sha256::new()));
+    assert!(!proof.verify(root, &transfer("carol", "alice", 2), &mut Sha256::new()));
+}
+
+#[test]
+fn test_sorted_struct_leaves() {
+    let config = Config { order: LeafOrder::ByValue, ..Config::default() };
+    let mt = MerkleTree::from_leaves_with_config(&transfers(), Sha256::new(), config).unwrap();
+    let proof = mt.non_membership_proof(&transfer("bob", "alice", 3)).unwrap();
+    assert!(proof.verify(mt.root_hash().unwrap(), &transfer("bob", "alice", 3), mt.len(), &config, &mut Sha256::new()));
+
+    let numbers: Vec<(u32, u8)> = vec![(3, 0), (1, 1), (2, 2)];
+    let mt = MerkleTree::from_leaves_with_config(&numbers, Sha256::new(), config).unwrap();
+    let sorted: Vec<(u32, u8)> = mt.leaves().map(|leaf| *leaf.value().unwrap()).collect();
+    assert_eq!(sorted, [(1, 1), (2, 2), (3, 0)]);
+}
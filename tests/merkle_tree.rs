@@ -0,0 +1,361 @@
+#![cfg(feature = "rust-crypto")]
+
+extern crate crypto;
+extern crate merkle_tree;
+
+use std::collections::HashMap;
+use std::io::{BufRead, Cursor};
+
+use crypto::sha2::Sha256;
+use merkle_tree::{Config, Hash, HashScheme, LeafOrder, MerkleTree, MerkleTreeBuilder, PaddingStrategy, Removal, StreamingRoot};
+
+fn drinks() -> Vec<String> {
+    vec![
+        String::from("tea"),
+        String::from("coffee"),
+        String::from("lemonade"),
+        String::from("wine"),
+        String::from("pepsi"),
+        String::from("cola")
+    ]
+}
+
+#[test]
+fn test_from_leaves() {
+    let leaf_values = drinks();
+    let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+
+    assert_eq!(mt.len(), 6);
+    assert!(!mt.is_empty());
+    assert_eq!(mt.count_leaves().unwrap(), 8);
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "a319d5ff88ec23b8694c166b086c5061f90794791b541f2b963138ebaaaa0ac8");
+
+    let root = mt.root().unwrap();
+    assert!(!root.is_leaf());
+    assert_eq!(root.value(), None);
+}
+
+#[test]
+fn test_from_leaves_empty() {
+    let leaf_values: Vec<String> = vec![];
+    assert!(MerkleTree::from_leaves(&leaf_values, Sha256::new()).is_err());
+}
+
+#[test]
+fn test_from_leaves_single() {
+    let leaf_values = vec![String::from("tea")];
+    let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+
+    assert_eq!(mt.len(), 1);
+    assert_eq!(mt.count_leaves().unwrap(), 1);
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "a9f74d1ec36ebdeb2da3f6e5868090cd2a2d20b3dcca7b62f60304b1d3d9ef42");
+}
+
+#[test]
+fn test_leaves() {
+    let leaf_values = drinks();
+    let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+
+    let values: Vec<&String> = mt.leaves().filter_map(|leaf| leaf.value()).collect();
+    assert_eq!(values, drinks().iter().collect::<Vec<_>>());
+
+    let tea = mt.leaf(0).unwrap();
+    assert!(tea.is_leaf());
+    assert_eq!(tea.value(), Some(&String::from("tea")));
+    assert_eq!(tea.hash().to_hex(), "a9f74d1ec36ebdeb2da3f6e5868090cd2a2d20b3dcca7b62f60304b1d3d9ef42");
+
+    // padding is not exposed as a leaf
+    assert!(mt.leaf(5).is_some());
+    assert!(mt.leaf(6).is_none());
+}
+
+#[test]
+fn test_add_leaves() {
+    let leaf_values = drinks();
+    let mut mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+
+    let new_values = vec![String::from("beer"), String::from("whisky")];
+    mt.add_leaves(&new_values).unwrap();
+
+    assert_eq!(mt.len(), 8);
+    assert_eq!(mt.count_leaves().unwrap(), 8);
+    assert_eq!(mt.leaf(7).and_then(|leaf| leaf.value()), Some(&String::from("whisky")));
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "66c67e65ac81acdb903f1a84940e1d45a5387e86b8da7d717cd96a9912ac3599");
+
+    // adding nothing leaves the tree untouched
+    mt.add_leaves(&[]).unwrap();
+    assert_eq!(mt.len(), 8);
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "66c67e65ac81acdb903f1a84940e1d45a5387e86b8da7d717cd96a9912ac3599");
+}
+
+#[test]
+fn test_add_leaves_grows_tree() {
+    let leaf_values = drinks();
+    let mut mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+
+    let mut grown_values = drinks();
+    grown_values.extend(vec![String::from("beer"), String::from("whisky"), String::from("rum")]);
+    let expected = MerkleTree::from_leaves(&grown_values, Sha256::new()).unwrap();
+
+    mt.add_leaves(&[String::from("beer"), String::from("whisky"), String::from("rum")]).unwrap();
+
+    assert_eq!(mt.len(), 9);
+    assert_eq!(mt.count_leaves().unwrap(), 16);
+    assert_eq!(mt.root_hash().unwrap(), expected.root_hash().unwrap());
+}
+
+#[test]
+fn test_proof() {
+    let leaf_values = drinks();
+    let mut mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+    mt.add_leaves(&[String::from("beer")]).unwrap();
+
+    let root = mt.root_hash().unwrap().clone();
+    let proof = mt.proof(6).unwrap();
+    assert!(proof.verify(&root, "beer", &mut Sha256::new()));
+    assert!(!proof.verify(&root, "cola", &mut Sha256::new()));
+    assert_eq!(proof.root("beer", &mut Sha256::new()), Ok(root));
+}
+
+#[test]
+fn test_multi_proof() {
+    let leaf_values = drinks();
+    let mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+    let root = mt.root_hash().unwrap();
+
+    let indices = [4, 1, 2];
+    let proof = mt.multi_proof(&indices).unwrap();
+    let leaves: Vec<String> = proof.indices().iter().map(|&i| drinks()[i].clone()).collect();
+    assert!(proof.verify(root, &leaves, mt.len(), mt.config(), &mut Sha256::new()));
+
+    let single_proofs: usize = indices.iter().map(|&i| mt.proof(i).unwrap().path().len()).sum();
+    assert!(proof.hashes().len() < single_proofs);
+}
+
+#[test]
+fn test_padding_strategy() {
+    let leaf_values = drinks();
+    let config = Config { padding: PaddingStrategy::Unbalanced, ..Config::default() };
+    let mut mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+
+    assert_eq!(mt.config().padding, PaddingStrategy::Unbalanced);
+    assert_eq!(mt.count_leaves().unwrap(), 6);
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "ab747701fa42c385dc8cfec8073b6b662da02e1ef401fdf831372e1531444933");
+
+    mt.add_leaves(&[String::from("beer")]).unwrap();
+    let mut grown_values = drinks();
+    grown_values.push(String::from("beer"));
+    let expected = MerkleTree::from_leaves_with_config(&grown_values, Sha256::new(), config).unwrap();
+    assert_eq!(mt.root_hash().unwrap(), expected.root_hash().unwrap());
+
+    let proof = mt.proof(6).unwrap();
+    assert!(proof.verify(mt.root_hash().unwrap(), "beer", &mut Sha256::new()));
+}
+
+#[test]
+fn test_add_leaf() {
+    let leaf_values = drinks();
+    let mut mt = MerkleTree::from_leaves(&leaf_values[..1], Sha256::new()).unwrap();
+    for value in &leaf_values[1..] {
+        mt.add_leaf(value).unwrap();
+    }
+
+    assert_eq!(mt.len(), 6);
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "a319d5ff88ec23b8694c166b086c5061f90794791b541f2b963138ebaaaa0ac8");
+}
+
+#[test]
+fn test_update_leaf() {
+    let mut leaf_values = drinks();
+    let mut mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+
+    leaf_values[2] = String::from("juice");
+    let root = mt.update_leaf(2, &leaf_values[2]).unwrap().clone();
+    let expected = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+    assert_eq!(&root, expected.root_hash().unwrap());
+
+    let proof = mt.proof(2).unwrap();
+    assert!(proof.verify(&root, "juice", &mut Sha256::new()));
+
+    let root = mt.update_leaves(&[(0, String::from("tea")), (2, String::from("lemonade"))]).unwrap();
+    assert_eq!(root.to_hex(), "a319d5ff88ec23b8694c166b086c5061f90794791b541f2b963138ebaaaa0ac8");
+    assert!(mt.update_leaf(6, &String::from("beer")).is_err());
+}
+
+#[test]
+fn test_remove_leaf() {
+    let leaf_values = drinks();
+    let mut mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+
+    let removed = mt.remove_leaf(1, Removal::Shift).unwrap();
+    assert_eq!(removed.value(), Some(&String::from("coffee")));
+    let removed = mt.remove_leaf(0, Removal::Swap).unwrap();
+    assert_eq!(removed.value(), Some(&String::from("tea")));
+
+    let remaining: Vec<String> = mt.leaves().map(|leaf| leaf.value().unwrap().clone()).collect();
+    assert_eq!(remaining, vec!["cola", "lemonade", "wine", "pepsi"]);
+    let expected = MerkleTree::from_leaves(&remaining, Sha256::new()).unwrap();
+    assert_eq!(mt.root_hash().unwrap(), expected.root_hash().unwrap());
+    assert_eq!(mt.count_leaves().unwrap(), 4);
+
+    assert!(mt.remove_leaf(4, Removal::Swap).is_err());
+}
+
+#[test]
+fn test_truncate() {
+    let leaf_values = drinks();
+    let mut mt = MerkleTree::from_leaves(&leaf_values, Sha256::new()).unwrap();
+
+    mt.truncate(4).unwrap();
+    assert_eq!(mt.len(), 4);
+    assert_eq!(mt.count_leaves().unwrap(), 4);
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "f327fcb35cf8b8a2bef2ef7a58695c914ab0f1dce982c57b9176886a29b86fc2");
+}
+
+#[test]
+fn test_non_membership_proof() {
+    let config = Config { order: LeafOrder::ByValue, ..Config::default() };
+    let mt = MerkleTree::from_leaves_with_config(&drinks(), Sha256::new(), config).unwrap();
+    let root = mt.root_hash().unwrap().clone();
+
+    let proof = mt.non_membership_proof(&String::from("milk")).unwrap();
+    assert_eq!(proof.left().unwrap().0, b"lemonade");
+    assert_eq!(proof.right().unwrap().0, b"pepsi");
+    assert!(proof.verify(&root, "milk", mt.len(), &config, &mut Sha256::new()));
+
+    assert!(mt.non_membership_proof(&String::from("tea")).is_err());
+
+    let unsorted = MerkleTree::from_leaves(&drinks(), Sha256::new()).unwrap();
+    assert!(unsorted.non_membership_proof(&String::from("milk")).is_err());
+}
+
+#[test]
+fn test_from_iter() {
+    let mt = MerkleTree::from_iter(drinks(), Sha256::new()).unwrap();
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "a319d5ff88ec23b8694c166b086c5061f90794791b541f2b963138ebaaaa0ac8");
+
+    let reader = Cursor::new("tea\ncoffee\nlemonade\nwine\npepsi\ncola\n");
+    let lines = reader.lines().map(Result::unwrap);
+    let config = Config { padding: PaddingStrategy::Unbalanced, ..Config::default() };
+    let mt = MerkleTree::from_iter_with_config(lines, Sha256::new(), config).unwrap();
+    let expected = MerkleTree::from_leaves_with_config(&drinks(), Sha256::new(), config).unwrap();
+    assert_eq!(mt.root_hash(), expected.root_hash());
+
+    let empty: Result<MerkleTree<Sha256, String>, _> = MerkleTree::from_iter(vec![], Sha256::new());
+    assert!(empty.is_err());
+}
+
+#[test]
+fn test_builder() {
+    let mut builder = MerkleTreeBuilder::new(Sha256::new(), Config::default()).unwrap();
+    assert!(builder.is_empty());
+    for line in Cursor::new("tea\ncoffee\nlemonade\n").lines() {
+        builder.push(line.unwrap()).unwrap();
+    }
+    builder.extend(vec![String::from("wine"), String::from("pepsi"), String::from("cola")]);
+    assert_eq!(builder.len(), 6);
+    assert!(builder.push_hash(Hash::new(&[0; 32])).is_err());
+    let mt = builder.build().unwrap();
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "a319d5ff88ec23b8694c166b086c5061f90794791b541f2b963138ebaaaa0ac8");
+    assert_eq!(mt.leaf(2).unwrap().value().unwrap(), "lemonade");
+
+    let config = Config { hash_only: true, ..Config::default() };
+    let mut builder = MerkleTreeBuilder::new(Sha256::new(), config).unwrap();
+    builder.extend(drinks().into_iter().take(5));
+    builder.push_hash(HashScheme::Unprefixed.hash_leaf("cola", &mut Sha256::new())).unwrap();
+    let mt = builder.build().unwrap();
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "a319d5ff88ec23b8694c166b086c5061f90794791b541f2b963138ebaaaa0ac8");
+    assert!(mt.leaves().all(|leaf| leaf.value().is_none()));
+
+    let config = Config { arity: 1, ..Config::default() };
+    assert!(MerkleTreeBuilder::<Sha256, String>::new(Sha256::new(), config).is_err());
+    assert!(MerkleTreeBuilder::<Sha256, String>::new(Sha256::new(), Config::default()).unwrap().build().is_err());
+}
+
+#[test]
+fn test_streaming_root() {
+    let mut streaming = StreamingRoot::new(Sha256::new(), Config::default()).unwrap();
+    for line in Cursor::new("tea\ncoffee\nlemonade\nwine\npepsi\ncola\n").lines() {
+        streaming.push(&line.unwrap()).unwrap();
+    }
+    assert_eq!(streaming.len(), 6);
+    assert_eq!(streaming.root().unwrap().to_hex(), "a319d5ff88ec23b8694c166b086c5061f90794791b541f2b963138ebaaaa0ac8");
+
+    let mut streaming = StreamingRoot::new(Sha256::new(), Config::rfc6962()).unwrap();
+    for value in drinks().iter() {
+        streaming.push(value).unwrap();
+    }
+    let expected = MerkleTree::from_leaves_with_config(&drinks(), Sha256::new(), Config::rfc6962()).unwrap();
+    assert_eq!(&streaming.root().unwrap(), expected.root_hash().unwrap());
+}
+
+#[test]
+fn test_hash_only() {
+    let leaf_values = drinks();
+    let mut hasher = Sha256::new();
+    let store: HashMap<Hash, String> = leaf_values.iter()
+        .map(|v| (HashScheme::Unprefixed.hash_leaf(v, &mut hasher), v.clone()))
+        .collect();
+    let hashes: Vec<Hash> = store.keys().cloned().collect();
+
+    let config = Config { order: LeafOrder::ByHash, ..Config::default() };
+    let mt: MerkleTree<Sha256, String> = MerkleTree::from_hashes(&hashes, Sha256::new(), config).unwrap();
+    let expected = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+    assert_eq!(mt.root_hash(), expected.root_hash());
+    assert!(mt.leaves().all(|leaf| leaf.value().is_none()));
+
+    for (i, leaf) in expected.leaves().enumerate() {
+        assert_eq!(mt.value(i, &store).as_ref(), Ok(leaf.value().unwrap()));
+    }
+
+    let root = mt.root_hash().unwrap().clone();
+    assert!(mt.non_membership_proof(&String::from("milk")).is_err());
+    let proof = mt.non_membership_proof_with_store(&String::from("milk"), &store).unwrap();
+    assert!(proof.verify(&root, "milk", mt.len(), &config, &mut Sha256::new()));
+}
+
+#[test]
+fn test_arity() {
+    let leaf_values: Vec<String> = (0..20).map(|i| format!("leaf-{}", i)).collect();
+    let roots = [
+        (4, "e0b4fe26e8532b7c2fc7552cb44386cf06a0a3e506a1f3973f533c4a60b619a3"),
+        (8, "8a6d06251a02eb77fb38c8bb446a3b2a1f7c3770e13708457dfa5469721131a8"),
+        (16, "40f48a2dd27ece1a5ce395952d6684e2f2f7120be5475c4df0e448048e4ce7a3"),
+    ];
+    for &(arity, root) in roots.iter() {
+        let config = Config { arity, ..Config::default() };
+        let mut mt = MerkleTree::from_leaves_with_config(&leaf_values, Sha256::new(), config).unwrap();
+        assert_eq!(mt.root_hash().unwrap().to_hex(), root);
+
+        for (i, value) in leaf_values.iter().enumerate() {
+            let proof = mt.kary_proof(i).unwrap();
+            assert!(proof.verify(mt.root_hash().unwrap(), value, &mut Sha256::new()));
+        }
+        assert!(mt.proof(0).is_err());
+        assert!(mt.multi_proof(&[0, 1]).is_err());
+
+        mt.add_leaf(&String::from("leaf-20")).unwrap();
+        if arity == 4 {
+            assert_eq!(mt.root_hash().unwrap().to_hex(), "4139ba13e29c235583d69627aa823837a1ff7e2dd19ec1365c82e8e6a1696109");
+        }
+    }
+
+    let drinks = drinks();
+    let config = Config { arity: 4, ..Config::default() };
+    let mt = MerkleTree::from_leaves_with_config(&drinks, Sha256::new(), config).unwrap();
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "fc1a3b9c683db4d6934f9bdf7642afe6f19dfc7bac60a5eb33162d0c416db977");
+    assert_eq!(mt.count_leaves().unwrap(), 16);
+
+    let config = Config { arity: 4, padding: PaddingStrategy::Zero, ..Config::default() };
+    let mt = MerkleTree::from_leaves_with_config(&drinks, Sha256::new(), config).unwrap();
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "5aa75b46bf722ac2fef89497fd33204f8819c2bcc49da78ed598fb5165f95c9d");
+
+    let config = Config { arity: 4, scheme: HashScheme::DomainSeparated, ..Config::default() };
+    let mt = MerkleTree::from_leaves_with_config(&drinks, Sha256::new(), config).unwrap();
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "5341e32d9868e577d4984b6e2e86b89ff601f5f81bf3ca84e30a20ec0c56943f");
+    let proof = mt.kary_proof(2).unwrap();
+    assert!(proof.verify_with_scheme(mt.root_hash().unwrap(), "lemonade", &mut Sha256::new(), HashScheme::DomainSeparated));
+
+    let single = MerkleTree::from_leaves_with_config(&leaf_values[..1], Sha256::new(), Config { arity: 16, ..Config::default() }).unwrap();
+    assert_eq!(single.root_hash().unwrap().to_hex(), "d2dbf006f96dd05044a8f63d8f118f23925ba4cc5750f8b6c8e287fd506c8188");
+}
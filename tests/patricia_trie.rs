@@ -0,0 +1,156 @@
+#![cfg(feature = "rust-crypto")]
+
+extern crate crypto;
+extern crate merkle_tree;
+extern crate serde_json;
+
+use crypto::sha3::Sha3;
+use merkle_tree::{Hash, PatriciaProof, PatriciaTrie, Rlp};
+use serde_json::Value;
+
+// Synthetic `eth_getProof` responses, in the JSON-RPC format, for a small
+// made-up state: the accounts and storage were generated offline and are not
+// mainnet data. The known-answer roots are the ethereum/tests cases below.
+const FIXTURE: &str = include_str!("fixtures/eth_get_proof.json");
+
+fn bytes(value: &Value) -> Vec<u8> {
+    let hex = value.as_str().unwrap().trim_start_matches("0x");
+    Hash::from_hex(hex).unwrap().as_bytes().to_vec()
+}
+
+// a hex quantity as RLP wants it: big-endian without leading zeros
+fn quantity(value: &Value) -> Vec<u8> {
+    let hex = value.as_str().unwrap().trim_start_matches("0x");
+    let padded = if hex.len() & 1 == 1 { format!("0{}", hex) } else { hex.to_string() };
+    let bytes = Hash::from_hex(&padded).unwrap().as_bytes().to_vec();
+    bytes.into_iter().skip_while(|&b| b == 0).collect()
+}
+
+fn keccak(bytes: &[u8]) -> Vec<u8> {
+    use crypto::digest::Digest;
+    let mut hasher = Sha3::keccak256();
+    hasher.input(bytes);
+    let mut out = vec![0u8; 32];
+    hasher.result(&mut out);
+    out
+}
+
+fn proof(key: Vec<u8>, nodes: &Value) -> PatriciaProof {
+    PatriciaProof::new(key, nodes.as_array().unwrap().iter().map(bytes).collect())
+}
+
+#[test]
+fn test_eth_get_proof() {
+    let fixture: Value = serde_json::from_str(FIXTURE).unwrap();
+    let state_root = Hash::new(&bytes(&fixture["stateRoot"]));
+    let proofs = fixture["proofs"].as_array().unwrap();
+
+    for response in proofs {
+        let account = Rlp::List(vec![
+            Rlp::Bytes(quantity(&response["nonce"])),
+            Rlp::Bytes(quantity(&response["balance"])),
+            Rlp::Bytes(bytes(&response["storageHash"])),
+            Rlp::Bytes(bytes(&response["codeHash"])),
+        ]);
+        let account_proof = proof(keccak(&bytes(&response["address"])), &response["accountProof"]);
+        let proven = account_proof.value(&state_root, &mut Sha3::keccak256()).unwrap();
+        match proven {
+            Some(encoded) => assert_eq!(Rlp::decode(&encoded).unwrap(), account),
+            None => assert!(quantity(&response["nonce"]).is_empty()),
+        }
+
+        let storage_root = Hash::new(&bytes(&response["storageHash"]));
+        for slot in response["storageProof"].as_array().unwrap() {
+            let storage_proof = proof(keccak(&bytes(&slot["key"])), &slot["proof"]);
+            let value = quantity(&slot["value"]);
+            let expected = if value.is_empty() { None } else { Some(Rlp::Bytes(value).encode()) };
+            assert!(storage_proof.verify(&storage_root, expected.as_deref(), &mut Sha3::keccak256()));
+        }
+    }
+}
+
+#[test]
+fn test_eth_get_proof_tampered() {
+    let fixture: Value = serde_json::from_str(FIXTURE).unwrap();
+    let state_root = Hash::new(&bytes(&fixture["stateRoot"]));
+    let response = &fixture["proofs"][0];
+    let address = keccak(&bytes(&response["address"]));
+
+    let mut nodes: Vec<Vec<u8>> = response["accountProof"].as_array().unwrap().iter().map(bytes).collect();
+    let last = nodes.len() - 1;
+    let byte = nodes[last].len() - 1;
+    nodes[last][byte] ^= 1;
+    let tampered = PatriciaProof::new(address.clone(), nodes);
+    assert!(tampered.value(&state_root, &mut Sha3::keccak256()).is_err());
+
+    let truncated = proof(address, &response["accountProof"]);
+    let truncated = PatriciaProof::new(truncated.key().to_vec(), truncated.nodes()[..1].to_vec());
+    assert!(truncated.value(&state_root, &mut Sha3::keccak256()).is_err());
+}
+
+#[test]
+fn test_state_root() {
+    let fixture: Value = serde_json::from_str(FIXTURE).unwrap();
+    let response = &fixture["proofs"][0];
+    let mut trie = PatriciaTrie::new(Sha3::keccak256());
+
+    // the storage the fixture's contract was generated with
+    let slots = [(0u64, 0x2au64), (1, 0xdeadbeef), (2, 1), (5, 1_000_000_000_000_000_000), (0x10, 7), (0x11, 8)];
+    for &(slot, value) in slots.iter() {
+        let mut key = [0u8; 32];
+        key[24..].copy_from_slice(&slot.to_be_bytes());
+        trie.insert(&keccak(&key), &Rlp::from_uint(value).encode());
+    }
+    assert_eq!(trie.root_hash().as_bytes(), &bytes(&response["storageHash"])[..]);
+}
+
+// Applies the updates in order, a null value deleting the key, as in the
+// trie tests of ethereum/tests.
+fn trie_test(updates: &[(&str, Option<&str>)]) -> String {
+    let mut trie = PatriciaTrie::new(Sha3::keccak256());
+    for (key, value) in updates {
+        match value {
+            Some(value) => trie.insert(key.as_bytes(), value.as_bytes()),
+            None => {
+                trie.remove(key.as_bytes());
+            }
+        }
+    }
+    trie.root_hash().to_hex()
+}
+
+// Cases from trietest.json and trieanyorder.json in ethereum/tests.
+#[test]
+fn test_ethereum_trie_tests() {
+    assert_eq!(trie_test(&[("foo", Some("bar")), ("food", Some("bass"))]),
+               "17beaa1648bafa633cda809c90c04af50fc8aed3cb40d16efbddee6fdf63c4c3");
+    assert_eq!(trie_test(&[("be", Some("e")), ("dog", Some("puppy")), ("bed", Some("d"))]),
+               "3f67c7a47520f79faa29255d2d3c084a7a6df0453116ed7232ff10277a8be68b");
+    assert_eq!(trie_test(&[("test", Some("test")), ("te", Some("testy"))]),
+               "8452568af70d8d140f58d941338542f645fcca50094b20f3c3d8c3df49337928");
+    assert_eq!(trie_test(&[("do", Some("verb")), ("ether", Some("wookiedoo")), ("horse", Some("stallion")),
+                           ("shaman", Some("horse")), ("doge", Some("coin")), ("ether", None),
+                           ("dog", Some("puppy")), ("shaman", None)]),
+               "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84");
+}
+
+// A block without transactions, such as mainnet block 1, has the empty trie
+// root as its transactionsRoot and receiptsRoot.
+#[test]
+fn test_ordered_empty_block() {
+    let mut trie = PatriciaTrie::ordered::<Vec<u8>>(&[], Sha3::keccak256());
+    assert_eq!(trie.root_hash().to_hex(), "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+}
+
+#[cfg(feature = "sha3")]
+#[test]
+fn test_keccak_backend() {
+    let mut trie = PatriciaTrie::new(merkle_tree::Keccak256::new());
+    for (key, value) in &[("doe", "reindeer"), ("dog", "puppy"), ("dogglesworth", "cat")] {
+        trie.insert(key.as_bytes(), value.as_bytes());
+    }
+    assert_eq!(trie.root_hash().to_hex(), "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3");
+
+    let root = trie.root_hash();
+    assert!(trie.proof(b"dog").verify(&root, Some(b"puppy"), &mut merkle_tree::Keccak256::new()));
+}
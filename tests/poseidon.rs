@@ -0,0 +1,109 @@
+#![cfg(feature = "poseidon")]
+
+extern crate merkle_tree;
+
+use merkle_tree::{Config, FieldElement, Hash, HashScheme, MerkleTree, Poseidon, SparseMerkleTree};
+
+fn elements() -> Vec<FieldElement> {
+    (1..=5).map(FieldElement::from_u64).collect()
+}
+
+fn root_of_hashes(leaves: &[FieldElement], arity: usize) -> String {
+    let hashes: Vec<Hash> = leaves.iter().map(FieldElement::to_hash).collect();
+    let config = Config { arity, ..Config::default() };
+    let mt = MerkleTree::<Poseidon, FieldElement>::from_hashes(&hashes, Poseidon::new(), config).unwrap();
+    mt.root_hash().unwrap().to_hex()
+}
+
+// Roots of full trees of zero leaves, the zero values of the Poseidon
+// incremental merkle tree in zk-kit (as used by Semaphore) at depths 1 to 5.
+#[test]
+fn test_zero_roots() {
+    let zeros = [
+        "2098f5fb9e239eab3ceac3f27b81e481dc3124d55ffed523a839ee8446b64864",
+        "1069673dcdb12263df301a6ff584a7ec261a44cb9dc68df067a4774460b1f1e1",
+        "18f43331537ee2af2e3d758d50f72106467c6eea50371dd528d57eb2b856d238",
+        "07f9d837cb17b0d36320ffe93ba52345f1b728571a568265caac97559dbc952a",
+        "2b94cf5e8746b3f5c9631f4c5df32907a699c58c94b2ad4d7b5cec1639183f55",
+    ];
+    for (depth, zero) in zeros.iter().enumerate() {
+        let leaves = vec![FieldElement::default(); 1 << (depth + 1)];
+        assert_eq!(root_of_hashes(&leaves, 2), *zero);
+    }
+}
+
+// One node of arity 4 over the leaves 1 to 4 is Poseidon([1, 2, 3, 4]),
+// the circomlibjs vector.
+#[test]
+fn test_arity_four_node() {
+    let leaves: Vec<FieldElement> = (1..=4).map(FieldElement::from_u64).collect();
+    assert_eq!(root_of_hashes(&leaves, 4), "299c867db6c1fdd79dcefa40e4510b9837e60ebb1ce0663dbaa525df65250465");
+}
+
+// Regression roots, as computed by this crate: they catch a change in how
+// leaves are hashed or padded, not a wrong one. The references are the
+// tests above and the circomlibjs vectors for the hash in src/poseidon.rs.
+#[test]
+fn test_root_hash() {
+    let mt = MerkleTree::from_leaves(&elements(), Poseidon::new()).unwrap();
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "291f62034abbf007e93d92d0cc4de3841398bb2ae51ab0178589e7335e1d62c0");
+    assert!(FieldElement::from_hash(mt.root_hash().unwrap()).is_ok());
+
+    let mt = MerkleTree::from_leaves_with_config(&elements(), Poseidon::new(), Config::rfc6962()).unwrap();
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "0925b43c67c7c0347c216105bcdb310c22b18ef180a2d4bc198b141ba54d336d");
+}
+
+#[test]
+fn test_not_field_elements() {
+    // 33 bytes, and 32 bytes above the modulus
+    let long = vec![1u8; 33];
+    let large = vec![0xffu8; 32];
+
+    assert!(MerkleTree::from_leaves(std::slice::from_ref(&long), Poseidon::new()).is_err());
+    assert!(MerkleTree::from_leaves(&[vec![1u8], large.clone()], Poseidon::new()).is_err());
+    assert!(MerkleTree::<Poseidon, Vec<u8>>::from_hashes(&[Hash::new(&large)], Poseidon::new(), Config::default()).is_err());
+
+    let mut mt = MerkleTree::from_leaves(&[vec![1u8], vec![2u8]], Poseidon::new()).unwrap();
+    let root = mt.root_hash().unwrap().clone();
+    assert!(mt.add_leaf(&long).is_err());
+    assert!(mt.update_leaf(0, &large).is_err());
+    assert_eq!(mt.root_hash(), Ok(&root));
+
+    let proof = mt.proof(0).unwrap();
+    assert!(proof.root(&long, &mut Poseidon::new()).is_err());
+    assert!(!proof.verify(&root, &large, &mut Poseidon::new()));
+
+    let mut smt = SparseMerkleTree::new(Poseidon::new());
+    smt.insert([1u8; 32], vec![1u8]).unwrap();
+    let root = smt.root_hash().clone();
+    assert!(smt.insert([2u8; 32], large).is_err());
+    assert_eq!(smt.root_hash(), &root);
+    assert_eq!(smt.len(), 1);
+}
+
+#[test]
+fn test_proof() {
+    let leaves = elements();
+    let mt = MerkleTree::from_leaves_with_config(&leaves, Poseidon::new(), Config::rfc6962()).unwrap();
+    let root = mt.root_hash().unwrap();
+
+    let mut hasher = Poseidon::new();
+    for (i, leaf) in leaves.iter().enumerate() {
+        let proof = mt.proof(i).unwrap();
+        assert!(proof.verify_with_scheme(root, leaf, &mut hasher, HashScheme::DomainSeparated));
+        assert!(!proof.verify_with_scheme(root, &FieldElement::from_u64(6), &mut hasher, HashScheme::DomainSeparated));
+    }
+}
+
+// Regression root, as above.
+#[test]
+fn test_arity_four() {
+    let config = Config { arity: 4, ..Config::default() };
+    let mt = MerkleTree::from_leaves_with_config(&elements(), Poseidon::new(), config).unwrap();
+    assert_eq!(mt.root_hash().unwrap().to_hex(), "000a018f7257b6b8ed1591e76ec766337fdecbe84aaaa9e6d1f3c9450704264d");
+
+    let root = mt.root_hash().unwrap();
+    for (i, leaf) in elements().iter().enumerate() {
+        assert!(mt.kary_proof(i).unwrap().verify(root, leaf, &mut Poseidon::new()));
+    }
+}
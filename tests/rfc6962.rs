@@ -0,0 +1,124 @@
+#![cfg(feature = "rust-crypto")]
+
+extern crate crypto;
+extern crate merkle_tree;
+
+use crypto::sha2::Sha256;
+use merkle_tree::{Config, ConsistencyProof, Hash, HashScheme, MerkleProof, MerkleTree};
+
+// Test vectors from the Certificate Transparency reference implementation.
+fn leaves() -> Vec<Vec<u8>> {
+    vec![
+        vec![],
+        vec![0x00],
+        vec![0x10],
+        vec![0x20, 0x21],
+        vec![0x30, 0x31],
+        vec![0x40, 0x41, 0x42, 0x43],
+        vec![0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57],
+        vec![0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x6b, 0x6c, 0x6d, 0x6e, 0x6f],
+    ]
+}
+
+const ROOTS: [&str; 8] = [
+    "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+    "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+    "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+    "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+    "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+    "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+    "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+    "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+];
+
+fn hashes(hex: &[&str]) -> Vec<Hash> {
+    hex.iter().map(|h| Hash::from_hex(h).unwrap()).collect()
+}
+
+#[test]
+fn test_tree_hash() {
+    let leaves = leaves();
+    for n in 1..=leaves.len() {
+        let mt = MerkleTree::from_leaves_with_config(&leaves[..n], Sha256::new(), Config::rfc6962()).unwrap();
+        assert_eq!(mt.root_hash().unwrap().to_hex(), ROOTS[n - 1]);
+    }
+}
+
+#[test]
+fn test_tree_hash_incremental() {
+    let leaves = leaves();
+    let mut mt = MerkleTree::from_leaves_with_config(&leaves[..1], Sha256::new(), Config::rfc6962()).unwrap();
+    for n in 2..=leaves.len() {
+        mt.add_leaves(&leaves[n - 1..n]).unwrap();
+        assert_eq!(mt.root_hash().unwrap().to_hex(), ROOTS[n - 1]);
+    }
+}
+
+#[test]
+fn test_audit_paths() {
+    let vectors: Vec<(usize, usize, Vec<&str>)> = vec![
+        (0, 1, vec![]),
+        (0, 8, vec![
+            "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7",
+            "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+            "6b47aaf29ee3c2af9af889bc1fb9254dabd31177f16232dd6aab035ca39bf6e4",
+        ]),
+        (5, 8, vec![
+            "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+            "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        ]),
+        (2, 3, vec![
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+        ]),
+        (1, 5, vec![
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+            "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+        ]),
+    ];
+
+    let leaves = leaves();
+    for (index, size, path) in vectors {
+        let mt = MerkleTree::from_leaves_with_config(&leaves[..size], Sha256::new(), Config::rfc6962()).unwrap();
+        let root = mt.root_hash().unwrap();
+        let proof = mt.proof(index).unwrap();
+        assert_eq!(proof.audit_path(), hashes(&path));
+
+        let proof = MerkleProof::from_audit_path(index, size, hashes(&path)).unwrap();
+        assert!(proof.verify_with_scheme(root, &leaves[index], &mut Sha256::new(), HashScheme::DomainSeparated));
+        assert!(!proof.verify(root, &leaves[index], &mut Sha256::new()));
+    }
+}
+
+#[test]
+fn test_consistency_proofs() {
+    let vectors: Vec<(usize, usize, Vec<&str>)> = vec![
+        (1, 1, vec![]),
+        (1, 8, vec![
+            "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7",
+            "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+            "6b47aaf29ee3c2af9af889bc1fb9254dabd31177f16232dd6aab035ca39bf6e4",
+        ]),
+        (6, 8, vec![
+            "0ebc5d3437fbe2db158b9f126a1d118e308181031d0a949f8dededebc558ef6a",
+            "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        ]),
+        (2, 5, vec![
+            "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+            "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+        ]),
+    ];
+
+    let mt = MerkleTree::from_leaves_with_config(&leaves(), Sha256::new(), Config::rfc6962()).unwrap();
+    for (old_size, new_size, proof) in vectors {
+        let old_root = Hash::from_hex(ROOTS[old_size - 1]).unwrap();
+        let new_root = Hash::from_hex(ROOTS[new_size - 1]).unwrap();
+
+        assert_eq!(mt.consistency_proof(old_size, new_size).unwrap().hashes(), &hashes(&proof)[..]);
+
+        let proof = ConsistencyProof::new(old_size, new_size, hashes(&proof));
+        assert!(proof.verify_with_scheme(&old_root, &new_root, &mut Sha256::new(), HashScheme::DomainSeparated));
+    }
+}
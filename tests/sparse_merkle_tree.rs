@@ -0,0 +1,44 @@
+#![cfg(feature = "rust-crypto")]
+
+extern crate crypto;
+extern crate merkle_tree;
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use merkle_tree::{HashScheme, SparseMerkleTree};
+
+fn key(name: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.input_str(name);
+    let mut key = [0u8; 32];
+    hasher.result(&mut key);
+    key
+}
+
+#[test]
+fn test_membership() {
+    let mut smt = SparseMerkleTree::new(Sha256::new());
+    smt.insert(key("alice"), String::from("tea")).unwrap();
+    smt.insert(key("bob"), String::from("coffee")).unwrap();
+    let root = smt.root_hash().clone();
+
+    let proof = smt.proof(&key("alice"));
+    assert_eq!(proof.key(), &key("alice"));
+    assert!(proof.verify(&root, Some("tea"), &mut Sha256::new()));
+    assert!(!proof.verify(&root, Some("coffee"), &mut Sha256::new()));
+}
+
+#[test]
+fn test_non_membership() {
+    let mut smt = SparseMerkleTree::with_scheme(Sha256::new(), HashScheme::DomainSeparated);
+    smt.insert(key("alice"), String::from("tea")).unwrap();
+    let root = smt.root_hash().clone();
+
+    let proof = smt.proof(&key("carol"));
+    assert!(proof.verify_with_scheme::<_, str>(&root, None, &mut Sha256::new(), HashScheme::DomainSeparated));
+    assert!(!proof.verify::<_, str>(&root, None, &mut Sha256::new()));
+
+    smt.remove(&key("alice"));
+    let proof = smt.proof(&key("alice"));
+    assert!(proof.verify_with_scheme::<_, str>(smt.root_hash(), None, &mut Sha256::new(), HashScheme::DomainSeparated));
+}